@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use anyhow::{bail, Result};
+
+use crate::collect::Collector;
+use crate::graph::{Edge, Graph};
+use crate::model::{self, Model};
+
+/// The result of the analysis of some source
+pub struct Analysis {
+    pub collector: Collector,
+    pub graph: Graph,
+    pub sinks: Sinks,
+    pub reachable: Reachable,
+}
+
+/// Types from which a sink is reachable, mapped to the sinks they reach
+pub type Reachable = BTreeMap<String, BTreeSet<String>>;
+
+/// Sink types of the analysis, each with the category it was selected by, or its own name
+/// when given directly
+pub type Sinks = BTreeMap<String, String>;
+
+/// Categories that exist even without the bundled model, as they are the default sinks
+const BUILTIN_CATEGORIES: &[(&str, &str)] =
+    &[("interior-mutability", "UnsafeCell"), ("raw-pointer", "rawptr")];
+
+/// Sinks for `specs`, each of which is a category of the model or a type name
+pub fn sinks(specs: &[String], model: &Model) -> Result<Sinks> {
+    let mut sinks = BTreeMap::new();
+    for spec in specs {
+        let builtin = BUILTIN_CATEGORIES.iter().find(|(c, _)| *c == spec.as_str());
+        match (model.categories.get(spec), builtin) {
+            (Some(types), _) => {
+                for t in types {
+                    sinks.insert(t.clone(), spec.clone());
+                }
+            }
+            (None, Some((_, t))) => {
+                sinks.insert(t.to_string(), spec.clone());
+            }
+            (None, None) if spec.is_empty() => bail!("empty sink"),
+            (None, None) => {
+                sinks.insert(spec.clone(), spec.clone());
+            }
+        }
+    }
+    Ok(sinks)
+}
+
+/// Types from which each sink is reachable, mapped to the sinks they reach. Sinks reach
+/// themselves.
+pub fn reachable(graph: &Graph, sinks: &Sinks) -> Reachable {
+    // the types depending on each type, shared by the searches of all sinks
+    let mut dependents: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for (k, es) in graph {
+        for e in es {
+            dependents.entry(&e.target).or_default().insert(k);
+        }
+    }
+    let mut reach = Reachable::new();
+    for sink in sinks.keys() {
+        for t in reaching(&dependents, sink) {
+            reach.entry(t.to_string()).or_default().insert(sink.clone());
+        }
+    }
+    reach
+}
+
+/// Types from which `sink` is reachable, found by a search from `sink` over `dependents`
+fn reaching<'a>(
+    dependents: &HashMap<&'a str, HashSet<&'a str>>,
+    sink: &'a str,
+) -> HashSet<&'a str> {
+    let mut reachable = HashSet::new();
+    reachable.insert(sink);
+    let mut queue = VecDeque::new();
+    queue.push_back(sink);
+    while let Some(t) = queue.pop_front() {
+        for &d in dependents.get(t).into_iter().flatten() {
+            if reachable.insert(d) {
+                queue.push_back(d);
+            }
+        }
+    }
+    reachable
+}
+
+/// Keeps only the types reachable from the types matching `roots`, patterns as in models
+pub fn restrict(graph: &Graph, reach: &mut Reachable, roots: &[String]) {
+    let mut visited: HashSet<&str> = graph
+        .keys()
+        .filter(|k| roots.iter().any(|p| model::matches(p, k)))
+        .map(|k| k.as_str())
+        .collect();
+    let mut queue: VecDeque<&str> = visited.iter().copied().collect();
+    while let Some(t) = queue.pop_front() {
+        for e in graph.get(t).into_iter().flatten() {
+            if visited.insert(&e.target) {
+                queue.push_back(&e.target);
+            }
+        }
+    }
+    *reach =
+        std::mem::take(reach).into_iter().filter(|(k, _)| visited.contains(k.as_str())).collect();
+}
+
+/// A shortest path of edges from `from` to `sink`, if any. Sinks are not gone through.
+pub fn shortest_path<'g>(
+    graph: &'g Graph,
+    reach: &Reachable,
+    from: &'g str,
+    sink: &str,
+) -> Option<Vec<&'g Edge>> {
+    // the edge through which each type was first reached, with its source
+    let mut prev: HashMap<&str, (&str, &Edge)> = HashMap::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(from);
+    while let Some(t) = queue.pop_front() {
+        if t == sink {
+            let mut path = vec![];
+            let mut cur = t;
+            while cur != from {
+                let (source, e) = prev[cur];
+                path.push(e);
+                cur = source;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if reach.get(t).map_or(true, |s| !s.contains(sink)) {
+            continue;
+        }
+        for e in graph.get(t).into_iter().flatten() {
+            let target = e.target.as_str();
+            if target != from && !prev.contains_key(target) {
+                prev.insert(target, (t, e));
+                queue.push_back(target);
+            }
+        }
+    }
+    None
+}
+
+/// Simple paths of edges from `from` to `sink`, at most `limit` of them. Sinks are not gone
+/// through.
+pub fn simple_paths<'g>(
+    graph: &'g Graph,
+    reach: &Reachable,
+    from: &str,
+    sink: &str,
+    limit: usize,
+) -> Vec<Vec<&'g Edge>> {
+    let mut paths = vec![];
+    let mut visited = HashSet::new();
+    visited.insert(from.to_string());
+    let mut path = vec![];
+    search(graph, reach, from, sink, limit, &mut visited, &mut path, &mut paths);
+    paths
+}
+
+#[allow(clippy::too_many_arguments)]
+fn search<'g>(
+    graph: &'g Graph,
+    reach: &Reachable,
+    t: &str,
+    sink: &str,
+    limit: usize,
+    visited: &mut HashSet<String>,
+    path: &mut Vec<&'g Edge>,
+    paths: &mut Vec<Vec<&'g Edge>>,
+) {
+    for e in graph.get(t).into_iter().flatten() {
+        if paths.len() >= limit {
+            return;
+        }
+        if e.target == sink {
+            path.push(e);
+            paths.push(path.clone());
+            path.pop();
+        } else if reach.get(&e.target).map_or(false, |s| s.contains(sink))
+            && visited.insert(e.target.clone())
+        {
+            path.push(e);
+            search(graph, reach, &e.target, sink, limit, visited, path, paths);
+            path.pop();
+            visited.remove(&e.target);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Violations of a policy accepted in a baseline file, so that only new ones fail the check
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::policy::{Policy, Violation};
+
+/// Sinks each type is accepted to reach, by rule id. Types are keyed by canonical path rather
+/// than location, so that moving definitions around keeps them accepted.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Baseline(BTreeMap<String, BTreeMap<String, BTreeSet<String>>>);
+
+impl Baseline {
+    /// The baseline accepting `violations`
+    pub fn new(policy: &Policy, violations: &[Violation]) -> Self {
+        let mut baseline = Baseline::default();
+        for v in violations {
+            let rule = policy.rules[v.rule].id.clone();
+            let types = baseline.0.entry(rule).or_default();
+            types.entry(v.ty.clone()).or_default().insert(v.sink.clone());
+        }
+        baseline
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("failed to read baseline {}", path.display()))?;
+        toml::from_str(&s).with_context(|| format!("invalid baseline {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let s = toml::to_string(self)?;
+        fs::write(path, s).with_context(|| format!("failed to write baseline {}", path.display()))
+    }
+
+    fn contains(&self, rule: &str, ty: &str, sink: &str) -> bool {
+        self.0.get(rule).and_then(|ts| ts.get(ty)).map_or(false, |ss| ss.contains(sink))
+    }
+
+    /// Marks the violations in the baseline as accepted
+    pub fn accept(&self, policy: &Policy, violations: &mut [Violation]) {
+        for v in violations {
+            v.accepted = self.contains(&policy.rules[v.rule].id, &v.ty, &v.sink);
+        }
+    }
+
+    /// The entries of the baseline that are no longer violated, as rule ids, types and sinks
+    pub fn stale<'a>(
+        &'a self,
+        policy: &Policy,
+        violations: &[Violation],
+    ) -> Vec<(&'a str, &'a str, &'a str)> {
+        let current = Baseline::new(policy, violations);
+        let mut stale = vec![];
+        for (rule, types) in &self.0 {
+            for (ty, sinks) in types {
+                for sink in sinks {
+                    if !current.contains(rule, ty, sink) {
+                        stale.push((rule.as_str(), ty.as_str(), sink.as_str()));
+                    }
+                }
+            }
+        }
+        stale
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    workspace_members: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    id: String,
+    name: String,
+    targets: Vec<RawTarget>,
+    dependencies: Vec<Dependency>,
+    features: HashMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct RawTarget {
+    name: String,
+    kind: Vec<String>,
+    src_path: PathBuf,
+    edition: String,
+}
+
+#[derive(Deserialize)]
+struct Dependency {
+    name: String,
+    rename: Option<String>,
+    kind: Option<String>,
+}
+
+/// A crate to analyze, built from a target of a workspace member
+pub struct Target {
+    /// Name of the root module, which prefixes the canonical paths of the crate's items
+    pub crate_name: String,
+    pub package: String,
+    pub name: String,
+    /// The first kind of the target, e.g. `lib`, `bin` or `test`
+    pub kind: String,
+    pub root: PathBuf,
+    /// Edition of the target, e.g. `2018`
+    pub edition: String,
+    /// Enabled features of the package
+    pub features: BTreeSet<String>,
+    /// Crates visible from this one, from the names they are used under to their `crate_name`
+    pub externs: HashMap<String, String>,
+}
+
+/// Features to enable, as with the corresponding cargo options
+#[derive(Default)]
+pub struct Features {
+    pub features: Vec<String>,
+    pub all: bool,
+    pub no_default: bool,
+}
+
+const LIB_KINDS: &[&str] = &["lib", "rlib", "dylib", "cdylib", "staticlib", "proc-macro"];
+
+/// Targets of the workspace whose manifest is `manifest`, discovered with `cargo metadata`.
+/// If `packages` or `targets` are non-empty, only the matching targets are returned, along
+/// with the workspace libraries they depend on.
+pub fn targets(
+    manifest: &Path,
+    packages: &[String],
+    targets: &[String],
+    features: &Features,
+) -> Result<Vec<Target>> {
+    let output = Command::new("cargo")
+        .args(&["metadata", "--format-version", "1", "--no-deps", "--manifest-path"])
+        .arg(manifest)
+        .output()
+        .context("failed to run `cargo metadata`")?;
+    if !output.status.success() {
+        bail!("`cargo metadata` failed:\n{}", String::from_utf8_lossy(&output.stderr));
+    }
+    let metadata: Metadata =
+        serde_json::from_slice(&output.stdout).context("invalid `cargo metadata` output")?;
+
+    let members: HashSet<&str> = metadata.workspace_members.iter().map(|s| s.as_str()).collect();
+    let packages_in_ws: Vec<&Package> =
+        metadata.packages.iter().filter(|p| members.contains(p.id.as_str())).collect();
+
+    // crate names of the libraries of the workspace members, by package name
+    let libs: HashMap<&str, String> = packages_in_ws
+        .iter()
+        .filter_map(|p| {
+            let lib = p.targets.iter().find(|t| is_lib(t))?;
+            Some((p.name.as_str(), crate_name(&lib.name)))
+        })
+        .collect();
+
+    let mut result = vec![];
+    for p in &packages_in_ws {
+        let lib = libs.get(p.name.as_str());
+        let enabled = enabled_features(p, features);
+        for t in &p.targets {
+            let kind = match t.kind.first() {
+                Some(k) if k == "custom-build" => continue,
+                Some(k) => k.as_str(),
+                None => continue,
+            };
+            let dev = matches!(kind, "test" | "example" | "bench");
+            let mut externs: HashMap<String, String> = p
+                .dependencies
+                .iter()
+                .filter(|d| match d.kind.as_deref() {
+                    None => true,
+                    Some("dev") => dev,
+                    _ => false,
+                })
+                .filter_map(|d| {
+                    let target = libs.get(d.name.as_str())?;
+                    let name = crate_name(d.rename.as_ref().unwrap_or(&d.name));
+                    Some((name, target.clone()))
+                })
+                .collect();
+            let crate_name = if is_lib(t) {
+                crate_name(&t.name)
+            } else {
+                let base = lib.cloned().unwrap_or_else(|| crate_name(&p.name));
+                if let Some(lib) = lib {
+                    externs.insert(lib.clone(), lib.clone());
+                }
+                format!("{}({} {})", base, kind, t.name)
+            };
+            result.push(Target {
+                crate_name,
+                package: p.name.clone(),
+                name: t.name.clone(),
+                kind: kind.to_string(),
+                root: t.src_path.clone(),
+                edition: t.edition.clone(),
+                features: enabled.clone(),
+                externs,
+            });
+        }
+    }
+
+    if packages.is_empty() && targets.is_empty() {
+        return Ok(result);
+    }
+
+    let selected = |t: &Target| {
+        (packages.is_empty() || packages.contains(&t.package))
+            && (targets.is_empty() || targets.contains(&t.name))
+    };
+    if !result.iter().any(selected) {
+        bail!("no target matches the given packages and targets");
+    }
+    let mut needed: HashSet<String> =
+        result.iter().filter(|t| selected(t)).map(|t| t.crate_name.clone()).collect();
+    loop {
+        let deps: Vec<String> = result
+            .iter()
+            .filter(|t| needed.contains(&t.crate_name))
+            .flat_map(|t| t.externs.values().cloned())
+            .filter(|c| !needed.contains(c))
+            .collect();
+        if deps.is_empty() {
+            break;
+        }
+        needed.extend(deps);
+    }
+    result.retain(|t| needed.contains(&t.crate_name));
+    Ok(result)
+}
+
+/// Features of `p` enabled by `features`, including those enabled by other features and
+/// optional dependencies, which define implicit features
+fn enabled_features(p: &Package, features: &Features) -> BTreeSet<String> {
+    if features.all {
+        return p.features.keys().cloned().collect();
+    }
+    let mut stack: Vec<&str> = features
+        .features
+        .iter()
+        .map(|f| f.as_str())
+        .filter(|f| p.features.contains_key(*f))
+        .collect();
+    if !features.no_default && p.features.contains_key("default") {
+        stack.push("default");
+    }
+    let mut enabled = BTreeSet::new();
+    while let Some(f) = stack.pop() {
+        if !enabled.insert(f.to_string()) {
+            continue;
+        }
+        for dep in p.features.get(f).into_iter().flatten() {
+            // `dep:x` and `x/f` name dependencies rather than features
+            if !dep.contains(':') && !dep.contains('/') {
+                stack.push(dep);
+            }
+        }
+    }
+    enabled
+}
+
+fn is_lib(t: &RawTarget) -> bool {
+    t.kind.iter().any(|k| LIB_KINDS.contains(&k.as_str()))
+}
+
+fn crate_name(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Edition of the package whose manifest is the closest to `path`, if any
+pub fn manifest_edition(path: &Path) -> Option<String> {
+    let path = path.canonicalize().ok()?;
+    let manifest = path.ancestors().map(|d| d.join("Cargo.toml")).find(|f| f.is_file())?;
+    let manifest: toml::Value = toml::from_str(&std::fs::read_to_string(manifest).ok()?).ok()?;
+    let package = manifest.get("package")?;
+    // cargo's default when the edition is not given
+    let edition = package.get("edition").and_then(|e| e.as_str()).unwrap_or("2015");
+    Some(edition.to_string())
+}
@@ -0,0 +1,297 @@
+use std::collections::HashSet;
+use std::mem;
+
+use anyhow::{bail, Result};
+use rustc_ast::{
+    attr::mk_attr_outer, ptr::P, Attribute, Crate, Item, ItemKind, MetaItemKind, ModKind,
+    NestedMetaItem, VariantData,
+};
+use rustc_span::symbol::sym;
+
+/// A set of active configuration options, against which `#[cfg(...)]` attributes are evaluated
+#[derive(Clone, Default)]
+pub struct Cfg {
+    options: HashSet<(String, Option<String>)>,
+}
+
+impl Cfg {
+    /// The options rustc sets when building for the host in the default profile
+    pub fn host() -> Self {
+        let mut cfg = Self::default();
+        let family = std::env::consts::FAMILY;
+        cfg.insert(family, None);
+        cfg.insert("target_family", Some(family));
+        cfg.insert("target_os", Some(std::env::consts::OS));
+        cfg.insert("target_arch", Some(std::env::consts::ARCH));
+        let env = if cfg!(target_env = "gnu") {
+            "gnu"
+        } else if cfg!(target_env = "msvc") {
+            "msvc"
+        } else if cfg!(target_env = "musl") {
+            "musl"
+        } else {
+            ""
+        };
+        cfg.insert("target_env", Some(env));
+        let vendor = if cfg!(target_vendor = "apple") {
+            "apple"
+        } else if cfg!(target_vendor = "pc") {
+            "pc"
+        } else {
+            "unknown"
+        };
+        cfg.insert("target_vendor", Some(vendor));
+        let width = (mem::size_of::<usize>() * 8).to_string();
+        cfg.insert("target_pointer_width", Some(width.as_str()));
+        let endian = if cfg!(target_endian = "little") { "little" } else { "big" };
+        cfg.insert("target_endian", Some(endian));
+        // `cfg!` cannot test the following on this toolchain: the default strategy, and the
+        // atomics of the usual hosts
+        cfg.insert("panic", Some("unwind"));
+        for size in &["8", "16", "32", "64", "ptr"] {
+            cfg.insert("target_has_atomic", Some(size));
+        }
+        cfg.insert("debug_assertions", None);
+        cfg
+    }
+
+    pub fn insert(&mut self, name: &str, value: Option<&str>) {
+        self.options.insert((name.to_string(), value.map(|v| v.to_string())));
+    }
+
+    /// Adds an option given as `name` or `name="value"`, as with rustc's `--cfg`
+    pub fn insert_spec(&mut self, spec: &str) -> Result<()> {
+        let (name, value) = match spec.split_once('=') {
+            Some((name, value)) => {
+                let value = value.trim();
+                if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+                    bail!("invalid cfg `{}`: the value must be a string literal", spec);
+                }
+                (name.trim(), Some(&value[1..value.len() - 1]))
+            }
+            None => (spec.trim(), None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            bail!("invalid cfg `{}`", spec);
+        }
+        self.insert(name, value);
+        Ok(())
+    }
+
+    /// Whether all `#[cfg(...)]` attributes among `attrs` hold
+    pub fn is_active(&self, attrs: &[Attribute]) -> bool {
+        attrs.iter().filter(|a| a.has_name(sym::cfg)).all(|a| match a.meta_item_list() {
+            Some(list) if list.len() == 1 => self.eval(&list[0]),
+            // malformed, so rustc would reject it anyway
+            _ => true,
+        })
+    }
+
+    /// Replaces the `#[cfg_attr(predicate, attributes...)]` among `attrs` with their attributes
+    /// when the predicate holds, and removes them otherwise
+    pub fn expand_attrs(&self, attrs: &mut Vec<Attribute>) {
+        if attrs.iter().any(|a| a.has_name(sym::cfg_attr)) {
+            *attrs = mem::take(attrs).into_iter().flat_map(|a| self.expand_attr(a)).collect();
+        }
+    }
+
+    fn expand_attr(&self, attr: Attribute) -> Vec<Attribute> {
+        if !attr.has_name(sym::cfg_attr) {
+            return vec![attr];
+        }
+        let list = match attr.meta_item_list() {
+            Some(list) if !list.is_empty() && self.eval(&list[0]) => list,
+            // false, or malformed so that rustc would reject it anyway
+            _ => return vec![],
+        };
+        let mut attrs: Vec<Attribute> = list[1..]
+            .iter()
+            .filter_map(|m| m.meta_item())
+            .map(|m| mk_attr_outer(m.clone()))
+            .collect();
+        // which may be `cfg_attr` again
+        self.expand_attrs(&mut attrs);
+        attrs
+    }
+
+    fn eval(&self, pred: &NestedMetaItem) -> bool {
+        let meta = match pred.meta_item() {
+            Some(meta) => meta,
+            None => return false,
+        };
+        let name = meta.name_or_empty().to_string();
+        match &meta.kind {
+            MetaItemKind::List(list) => match name.as_str() {
+                "all" => list.iter().all(|p| self.eval(p)),
+                "any" => list.iter().any(|p| self.eval(p)),
+                "not" => list.len() == 1 && !self.eval(&list[0]),
+                _ => false,
+            },
+            MetaItemKind::Word => self.options.contains(&(name, None)),
+            MetaItemKind::NameValue(_) => match meta.value_str() {
+                Some(v) => self.options.contains(&(name, Some(v.to_string()))),
+                None => false,
+            },
+        }
+    }
+}
+
+/// Removes the items, associated items, fields and variants of `krate` that are configured out,
+/// and expands the `cfg_attr` attributes of the crate and of the others
+pub fn strip_crate(krate: &mut Crate, cfg: &Cfg) {
+    cfg.expand_attrs(&mut krate.attrs);
+    strip_items(&mut krate.items, cfg);
+}
+
+/// Expands the `cfg_attr` attributes of `nodes`, which `attrs` gives, and removes the nodes
+/// that are configured out
+pub fn retain_active<T>(
+    nodes: &mut Vec<T>,
+    cfg: &Cfg,
+    attrs: impl Fn(&mut T) -> &mut Vec<Attribute>,
+) {
+    let mut active = nodes
+        .iter_mut()
+        .map(|n| {
+            let attrs = attrs(n);
+            cfg.expand_attrs(attrs);
+            cfg.is_active(attrs)
+        })
+        .collect::<Vec<_>>()
+        .into_iter();
+    nodes.retain(|_| active.next().unwrap());
+}
+
+pub fn strip_items(items: &mut Vec<P<Item>>, cfg: &Cfg) {
+    retain_active(items, cfg, |i| &mut i.attrs);
+    for item in items {
+        match &mut item.kind {
+            ItemKind::Struct(data, _) | ItemKind::Union(data, _) => strip_fields(data, cfg),
+            ItemKind::Enum(def, _) => {
+                retain_active(&mut def.variants, cfg, |v| &mut v.attrs);
+                for v in &mut def.variants {
+                    strip_fields(&mut v.data, cfg);
+                }
+            }
+            ItemKind::ForeignMod(m) => retain_active(&mut m.items, cfg, |i| &mut i.attrs),
+            ItemKind::Impl(kind) => retain_active(&mut kind.items, cfg, |i| &mut i.attrs),
+            ItemKind::Trait(kind) => retain_active(&mut kind.4, cfg, |i| &mut i.attrs),
+            ItemKind::Mod(_, ModKind::Loaded(items, ..)) => strip_items(items, cfg),
+            _ => {}
+        }
+    }
+}
+
+fn strip_fields(data: &mut VariantData, cfg: &Cfg) {
+    match data {
+        VariantData::Struct(fields, _) | VariantData::Tuple(fields, _) => {
+            retain_active(fields, cfg, |f| &mut f.attrs)
+        }
+        VariantData::Unit(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rustc_ast::attr::first_attr_value_str_by_name;
+    use rustc_session::parse::ParseSess;
+    use rustc_span::{edition::Edition, source_map::FilePathMapping};
+
+    use super::*;
+
+    /// The crate whose root file contains `src`, stripped under `cfg`
+    fn strip<R>(src: &str, cfg: &Cfg, f: impl FnOnce(&Crate) -> R) -> R {
+        rustc_span::with_session_globals(Edition::Edition2018, || {
+            let sess = ParseSess::new(FilePathMapping::empty());
+            let name = PathBuf::from("lib.rs").into();
+            let mut krate = match rustc_parse::parse_crate_from_source_str(name, src.into(), &sess)
+            {
+                Ok(krate) => krate,
+                Err(mut e) => {
+                    e.emit();
+                    panic!("invalid source");
+                }
+            };
+            strip_crate(&mut krate, cfg);
+            f(&krate)
+        })
+    }
+
+    /// Whether `pred` holds under `cfg`
+    fn holds(cfg: &Cfg, pred: &str) -> bool {
+        strip(&format!("#[cfg({})] struct S;", pred), cfg, |k| !k.items.is_empty())
+    }
+
+    fn cfg(specs: &[&str]) -> Cfg {
+        let mut cfg = Cfg::default();
+        for spec in specs {
+            cfg.insert_spec(spec).unwrap();
+        }
+        cfg
+    }
+
+    #[test]
+    fn names_and_values() {
+        let cfg = cfg(&["test", "feature=\"serde\""]);
+        assert!(holds(&cfg, "test"));
+        assert!(!holds(&cfg, "unix_like"));
+        assert!(holds(&cfg, "feature = \"serde\""));
+        assert!(!holds(&cfg, "feature = \"std\""));
+        assert!(!holds(&cfg, "feature"));
+        assert!(!holds(&cfg, "test = \"serde\""));
+    }
+
+    #[test]
+    fn all_any_not() {
+        let cfg = cfg(&["a", "b"]);
+        assert!(holds(&cfg, "all(a, b)"));
+        assert!(!holds(&cfg, "all(a, c)"));
+        assert!(holds(&cfg, "any(c, b)"));
+        assert!(!holds(&cfg, "any(c, d)"));
+        assert!(holds(&cfg, "not(c)"));
+        assert!(!holds(&cfg, "not(a)"));
+        assert!(holds(&cfg, "all(a, not(any(c, d)))"));
+        assert!(holds(&cfg, "all()"));
+        assert!(!holds(&cfg, "any()"));
+    }
+
+    #[test]
+    fn invalid_specs() {
+        let mut cfg = Cfg::default();
+        for spec in &["feature=serde", "", "a-b", "feature=\""] {
+            assert!(cfg.insert_spec(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn fields_and_variants() {
+        let src = "struct S { #[cfg(a)] x: u8, #[cfg(b)] y: u8 } enum E { #[cfg(b)] V }";
+        let (fields, variants) = strip(src, &cfg(&["a"]), |k| {
+            let fields = match &k.items[0].kind {
+                ItemKind::Struct(data, _) => data.fields().len(),
+                _ => unreachable!(),
+            };
+            let variants = match &k.items[1].kind {
+                ItemKind::Enum(def, _) => def.variants.len(),
+                _ => unreachable!(),
+            };
+            (fields, variants)
+        });
+        assert_eq!((fields, variants), (1, 0));
+    }
+
+    #[test]
+    fn cfg_attr() {
+        let cfg = cfg(&["a"]);
+        let src = "#[cfg_attr(a, cfg(b))] struct S; #[cfg_attr(b, cfg(b))] struct T;";
+        let names = strip(src, &cfg, |k| k.items.iter().map(|i| i.ident.to_string()).collect());
+        assert_eq!(names, vec!["T".to_string()]);
+        let src = "#[cfg_attr(a, cfg_attr(all(), path = \"x.rs\", allow(dead_code)))] mod m;";
+        let path = strip(src, &cfg, |k| {
+            first_attr_value_str_by_name(&k.items[0].attrs, sym::path).map(|p| p.to_string())
+        });
+        assert_eq!(path.as_deref(), Some("x.rs"));
+    }
+}
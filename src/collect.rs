@@ -0,0 +1,687 @@
+use std::ops::Deref;
+use std::path::Path as FilePath;
+use std::rc::Rc;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+
+use rustc_ast::{
+    ptr::P,
+    visit::{walk_crate, walk_fn, walk_foreign_item, walk_item, FnKind, Visitor},
+    AngleBracketedArg, AssocItemKind, AssocTyConstraintKind, Attribute, BareFnTy, Crate, FnDecl,
+    FnRetTy, ForeignItem, ForeignItemKind, GenericArg, GenericArgs, GenericBound, GenericParam,
+    GenericParamKind, Generics, Item, ItemKind, MetaItem, MetaItemKind, MutTy, Mutability,
+    NestedMetaItem, NodeId, Path, QSelf, Ty, TyAliasKind, TyKind, UseTree, UseTreeKind,
+    VariantData,
+};
+use rustc_session::parse::ParseSess;
+use rustc_span::{source_map::SourceMap, symbol::Ident, Span};
+
+use crate::cfg::{self, Cfg};
+use crate::graph::{Location, Wrapper};
+use crate::loader;
+
+/// A type path as written in the source, one entry per segment
+pub type TypePath = Vec<String>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Struct,
+    Enum,
+    Union,
+    TyAlias,
+    ForeignType,
+    Trait,
+}
+
+impl NodeKind {
+    pub fn dot_style(self) -> &'static str {
+        match self {
+            NodeKind::Struct => "",
+            NodeKind::Enum => ", shape=box",
+            NodeKind::Union => ", shape=box, style=dashed",
+            NodeKind::TyAlias => ", style=dotted",
+            NodeKind::ForeignType => ", shape=box, style=filled",
+            NodeKind::Trait => ", shape=hexagon",
+        }
+    }
+}
+
+impl fmt::Display for NodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            NodeKind::Struct => "struct",
+            NodeKind::Enum => "enum",
+            NodeKind::Union => "union",
+            NodeKind::TyAlias => "type",
+            NodeKind::ForeignType => "extern type",
+            NodeKind::Trait => "trait",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A type definition found in the crate
+pub struct Node {
+    pub kind: NodeKind,
+    /// Path of the module the definition lives in, starting with the name of the crate's root
+    /// module, `crate` unless analyzing a workspace
+    pub module: Vec<String>,
+    pub location: Option<Location>,
+    /// Location of the end of the definition
+    pub end: Option<Location>,
+    /// Generic parameters, e.g. `'a`, `T` or `const N`
+    pub generics: Vec<String>,
+    /// Attributes other than doc comments, e.g. `derive(Clone, Debug)` or `repr(C)`
+    pub attrs: Vec<String>,
+    pub refs: Vec<Ref>,
+}
+
+/// A reference to a type in a definition
+pub struct Ref {
+    pub path: TypePath,
+    /// Constructs of the type expression the reference is nested in, outermost first
+    pub wrappers: Vec<Wrapper>,
+    /// Name of the field containing the reference, or its index in a tuple struct or variant
+    pub field: Option<String>,
+    /// Variant the field belongs to, in an enum
+    pub variant: Option<String>,
+    pub location: Option<Location>,
+    /// The projection the reference is, in which case `path` is the path written after the
+    /// qualified self type
+    pub projection: Option<Projection>,
+}
+
+/// An associated type projection, e.g. `<T as Iterator>::Item`, on a self type written as a path
+#[derive(Clone)]
+pub struct Projection {
+    pub self_ty: TypePath,
+    /// `None` for the shorthand `Self::Item` in an impl block, which any trait implemented by
+    /// the self type may define
+    pub trait_: Option<TypePath>,
+    pub name: String,
+}
+
+/// An `impl` block whose self type is a path, or a trait definition, with the associated types
+/// and constants it defines
+pub struct Impl {
+    /// Path of the module the block or definition is in
+    pub module: Vec<String>,
+    /// Self type of an impl block, `None` for a trait definition
+    pub self_ty: Option<TypePath>,
+    /// Trait of a trait impl, or the defined trait
+    pub trait_: Option<TypePath>,
+    pub location: Option<Location>,
+    pub items: Vec<AssocItem>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AssocKind {
+    Type,
+    Const,
+}
+
+/// An associated type or constant
+pub struct AssocItem {
+    pub kind: AssocKind,
+    pub name: String,
+    /// References in the type of a constant, or in the type an associated type is defined as,
+    /// which is the default one in a trait definition
+    pub refs: Vec<Ref>,
+}
+
+/// A `use` declaration, flattened so that each import has a single path
+pub struct Import {
+    /// Path of the module the declaration is in
+    pub module: Vec<String>,
+    /// Imported path as written, including any `self`/`super`/`crate` prefix
+    pub path: Vec<String>,
+    pub kind: ImportKind,
+}
+
+pub enum ImportKind {
+    /// Binds the last segment of the path, or the given rename
+    Single(String),
+    Glob,
+}
+
+#[derive(Default)]
+pub struct Collector {
+    /// Definitions keyed by their canonical path
+    pub items: BTreeMap<String, Node>,
+    /// Canonical paths of all modules
+    pub modules: HashSet<String>,
+    pub imports: Vec<Import>,
+    /// Impl blocks and trait definitions, in the order they are found
+    pub impls: Vec<Impl>,
+    /// Crates visible from each crate, by the name of its root module. See `cargo::Target`.
+    pub externs: HashMap<String, HashMap<String, String>>,
+    module: Vec<String>,
+    source_map: Option<Rc<SourceMap>>,
+    /// Number of function bodies the visited item is in
+    bodies: usize,
+}
+
+impl Collector {
+    /// Parses `file` and collects its definitions, treating it as the module `file` is for
+    /// relative to the source directory `dir`
+    pub fn collect(&mut self, dir: &FilePath, file: &FilePath, sess: &ParseSess, cfg: &Cfg) {
+        let mut krate = match loader::parse_file(file, sess) {
+            Some(krate) => krate,
+            None => return,
+        };
+        cfg::strip_crate(&mut krate, cfg);
+        if cfg.is_active(&krate.attrs) {
+            self.collect_crate(module_path(dir, file), &krate, sess);
+        }
+    }
+
+    /// Collects the definitions in `krate`, which is the module `module` and was parsed in
+    /// `sess`
+    pub fn collect_crate(&mut self, module: Vec<String>, krate: &Crate, sess: &ParseSess) {
+        self.module = module;
+        for i in 1..=self.module.len() {
+            self.modules.insert(self.module[..i].join("::"));
+        }
+        self.source_map = Some(sess.clone_source_map());
+        walk_crate(self, krate);
+        self.source_map = None;
+    }
+
+    fn insert<K>(
+        &mut self,
+        item: &rustc_ast::Item<K>,
+        kind: NodeKind,
+        generics: Option<&Generics>,
+        refs: Vec<Ref>,
+    ) {
+        let mut path = self.module.clone();
+        path.push(item.ident.to_string());
+        let k = path.join("::");
+        let node = Node {
+            kind,
+            module: self.module.clone(),
+            location: self.location(item.span),
+            end: self.location(item.span.shrink_to_hi()),
+            generics: generics
+                .map_or_else(Vec::new, |g| g.params.iter().map(generic_param_string).collect()),
+            attrs: item.attrs.iter().filter_map(attr_string).collect(),
+            refs: shorthands(refs, &generics.map_or_else(Vec::new, type_params), None),
+        };
+        let location = node.location.clone();
+        if let Some(v) = self.items.insert(k.clone(), node) {
+            // on stderr, as stdout may hold the output
+            let at = |l: Option<Location>| l.map_or_else(String::new, |l| format!(" at {}", l));
+            eprintln!(
+                "warning: `{}`{} is defined again{}, which replaces it",
+                k,
+                at(v.location),
+                at(location)
+            );
+        }
+    }
+
+    /// Records the associated types and constants among `items`, those of an impl block or
+    /// trait definition `item`
+    fn insert_impl(
+        &mut self,
+        item: &Item,
+        generics: &Generics,
+        self_ty: Option<TypePath>,
+        trait_: Option<TypePath>,
+        items: &[P<rustc_ast::AssocItem>],
+    ) {
+        let params = type_params(generics);
+        let items = items
+            .iter()
+            .filter_map(|i| {
+                let (kind, refs) = match &i.kind {
+                    AssocItemKind::TyAlias(alias) => (AssocKind::Type, self.refs(&**alias)),
+                    AssocItemKind::Const(_, ty, _) => (AssocKind::Const, self.refs(&**ty)),
+                    _ => return None,
+                };
+                let refs = shorthands(refs, &params, self_ty.as_ref());
+                Some(AssocItem { kind, name: i.ident.to_string(), refs })
+            })
+            .collect();
+        let location = self.location(item.span);
+        self.impls.push(Impl { module: self.module.clone(), self_ty, trait_, location, items });
+    }
+
+    fn location(&self, span: Span) -> Option<Location> {
+        let loc = self.source_map.as_ref()?.lookup_char_pos(span.lo());
+        Some(Location { file: loc.file.name.to_string(), line: loc.line, col: loc.col.0 + 1 })
+    }
+
+    /// References in the fields of a struct, union or enum variant
+    fn field_refs(&self, data: &VariantData, variant: Option<Ident>) -> Vec<Ref> {
+        let mut refs = vec![];
+        for (i, f) in data.fields().iter().enumerate() {
+            let field = f.ident.map_or_else(|| i.to_string(), |id| id.to_string());
+            for r in f.ty.all_type_refs() {
+                refs.push(Ref {
+                    path: r.path,
+                    wrappers: r.wrappers,
+                    field: Some(field.clone()),
+                    variant: variant.map(|v| v.to_string()),
+                    location: self.location(r.span),
+                    projection: r.projection,
+                });
+            }
+        }
+        refs
+    }
+
+    /// References outside fields, e.g. in the aliased type of a type alias
+    fn refs<T: ContainTypes>(&self, t: &T) -> Vec<Ref> {
+        t.all_type_refs()
+            .into_iter()
+            .map(|r| Ref {
+                path: r.path,
+                wrappers: r.wrappers,
+                field: None,
+                variant: None,
+                location: self.location(r.span),
+                projection: r.projection,
+            })
+            .collect()
+    }
+
+    fn record_use(&mut self, tree: &UseTree, prefix: &[String]) {
+        let mut path = prefix.to_vec();
+        path.extend(tree.prefix.segments.iter().map(|s| s.ident.to_string()));
+        match &tree.kind {
+            UseTreeKind::Simple(rename, ..) => {
+                if path.last().map(|s| s.as_str()) == Some("self") {
+                    path.pop();
+                }
+                let name = match (rename, path.last()) {
+                    (Some(r), _) => r.to_string(),
+                    (None, Some(n)) => n.clone(),
+                    (None, None) => return,
+                };
+                if name != "_" {
+                    let module = self.module.clone();
+                    self.imports.push(Import { module, path, kind: ImportKind::Single(name) });
+                }
+            }
+            UseTreeKind::Nested(trees) => {
+                for (t, _) in trees {
+                    self.record_use(t, &path);
+                }
+            }
+            UseTreeKind::Glob => {
+                let module = self.module.clone();
+                self.imports.push(Import { module, path, kind: ImportKind::Glob });
+            }
+        }
+    }
+}
+
+fn generic_param_string(param: &GenericParam) -> String {
+    match param.kind {
+        GenericParamKind::Const { .. } => format!("const {}", param.ident),
+        _ => param.ident.to_string(),
+    }
+}
+
+/// Names of the type parameters among `generics`
+fn type_params(generics: &Generics) -> Vec<String> {
+    let params = generics.params.iter().filter(|p| matches!(p.kind, GenericParamKind::Type { .. }));
+    params.map(|p| p.ident.to_string()).collect()
+}
+
+/// Replaces the shorthand projections among `refs`, paths starting with one of the type
+/// parameters `params` or with `Self`, e.g. `I::Item`, which do not name a type. Those on `Self`
+/// become projections on `self_ty` when it is known, and the others are removed as the actual
+/// type is unknown.
+fn shorthands(refs: Vec<Ref>, params: &[String], self_ty: Option<&TypePath>) -> Vec<Ref> {
+    refs.into_iter()
+        .filter_map(|mut r| {
+            if r.path.len() < 2 || r.projection.is_some() {
+                return Some(r);
+            }
+            let on_self = r.path[0] == "Self";
+            if !on_self && !params.contains(&r.path[0]) {
+                return Some(r);
+            }
+            match self_ty {
+                Some(ty) if on_self && r.path.len() == 2 => {
+                    let name = r.path.pop().unwrap();
+                    r.wrappers.push(Wrapper::Projection { ty: None, name: name.clone() });
+                    r.path = vec![name.clone()];
+                    r.projection = Some(Projection { self_ty: ty.clone(), trait_: None, name });
+                    Some(r)
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn attr_string(attr: &Attribute) -> Option<String> {
+    if attr.is_doc_comment() {
+        return None;
+    }
+    attr.meta().map(|m| meta_string(&m))
+}
+
+fn meta_string(meta: &MetaItem) -> String {
+    let path: Vec<String> = meta.path.segments.iter().map(|s| s.ident.to_string()).collect();
+    let path = path.join("::");
+    match &meta.kind {
+        MetaItemKind::Word => path,
+        MetaItemKind::List(list) => {
+            let list: Vec<String> = list
+                .iter()
+                .map(|m| match m {
+                    NestedMetaItem::MetaItem(m) => meta_string(m),
+                    NestedMetaItem::Literal(l) => l.token.to_string(),
+                })
+                .collect();
+            format!("{}({})", path, list.join(", "))
+        }
+        MetaItemKind::NameValue(l) => format!("{} = {}", path, l.token),
+    }
+}
+
+/// Module path of a file in the usual `foo.rs`/`foo/mod.rs` layout rooted at `dir`
+fn module_path(dir: &FilePath, file: &FilePath) -> Vec<String> {
+    let rel = file.strip_prefix(dir).unwrap_or(file);
+    let mut module = vec!["crate".to_string()];
+    let mut components: Vec<String> =
+        rel.iter().map(|c| c.to_string_lossy().into_owned()).collect();
+    if let Some(last) = components.pop() {
+        let stem = last.trim_end_matches(".rs");
+        module.extend(components);
+        let root = module.len() == 1 && (stem == "lib" || stem == "main");
+        if stem != "mod" && !root {
+            module.push(stem.to_string());
+        }
+    }
+    module
+}
+
+impl<'ast> Visitor<'ast> for Collector {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match &item.kind {
+            ItemKind::Struct(data, generics) => {
+                self.insert(item, NodeKind::Struct, Some(generics), self.field_refs(data, None))
+            }
+            ItemKind::Union(data, generics) => {
+                self.insert(item, NodeKind::Union, Some(generics), self.field_refs(data, None))
+            }
+            ItemKind::Enum(def, generics) => {
+                let refs =
+                    def.variants.iter().flat_map(|v| self.field_refs(&v.data, Some(v.ident)));
+                let refs = refs.collect();
+                self.insert(item, NodeKind::Enum, Some(generics), refs)
+            }
+            ItemKind::TyAlias(kind) => {
+                self.insert(item, NodeKind::TyAlias, Some(&kind.1), self.refs(&**kind))
+            }
+            ItemKind::Trait(kind) => {
+                self.insert(item, NodeKind::Trait, Some(&kind.2), vec![]);
+                let trait_ = Some(vec![item.ident.to_string()]);
+                self.insert_impl(item, &kind.2, None, trait_, &kind.4)
+            }
+            ItemKind::Impl(kind) => {
+                if let TyKind::Path(None, p) = &kind.self_ty.kind {
+                    let trait_ = kind.of_trait.as_ref().map(|t| segments(&t.path));
+                    self.insert_impl(item, &kind.generics, Some(segments(p)), trait_, &kind.items)
+                }
+            }
+            // imports in function bodies are only visible there
+            ItemKind::Use(tree) if self.bodies == 0 => self.record_use(tree, &[]),
+            ItemKind::Mod(..) => {
+                self.module.push(item.ident.to_string());
+                self.modules.insert(self.module.join("::"));
+                walk_item(self, item);
+                self.module.pop();
+                return;
+            }
+            _ => {}
+        }
+
+        walk_item(self, item);
+    }
+
+    fn visit_fn(&mut self, kind: FnKind<'ast>, span: Span, _: NodeId) {
+        self.bodies += 1;
+        walk_fn(self, kind, span);
+        self.bodies -= 1;
+    }
+
+    fn visit_foreign_item(&mut self, item: &'ast ForeignItem) {
+        if let ForeignItemKind::TyAlias(_) = &item.kind {
+            self.insert(item, NodeKind::ForeignType, None, vec![]);
+        }
+
+        walk_foreign_item(self, item);
+    }
+}
+
+/// A type referenced in a type expression
+struct TypeRef {
+    path: TypePath,
+    /// Constructs of the type expression the reference is nested in, outermost first
+    wrappers: Vec<Wrapper>,
+    span: Span,
+    projection: Option<Projection>,
+}
+
+trait ContainTypes {
+    /// Adds the types referenced in `self` to `out`, nested in `wrappers` and then in the
+    /// constructs of `self` they are in
+    fn type_refs(&self, wrappers: &mut Vec<Wrapper>, out: &mut Vec<TypeRef>);
+
+    fn all_type_refs(&self) -> Vec<TypeRef> {
+        let mut out = vec![];
+        self.type_refs(&mut vec![], &mut out);
+        out
+    }
+}
+
+fn nested<T: ContainTypes>(
+    t: &T,
+    wrapper: Wrapper,
+    wrappers: &mut Vec<Wrapper>,
+    out: &mut Vec<TypeRef>,
+) {
+    wrappers.push(wrapper);
+    t.type_refs(wrappers, out);
+    wrappers.pop();
+}
+
+fn marker(name: &str, span: Span, wrappers: &[Wrapper]) -> TypeRef {
+    TypeRef { path: vec![name.to_string()], wrappers: wrappers.to_vec(), span, projection: None }
+}
+
+fn segments(path: &Path) -> TypePath {
+    path.segments.iter().map(|s| s.ident.to_string()).collect()
+}
+
+impl ContainTypes for Ty {
+    fn type_refs(&self, wrappers: &mut Vec<Wrapper>, out: &mut Vec<TypeRef>) {
+        match &self.kind {
+            TyKind::Slice(ty) => nested(&**ty, Wrapper::Slice, wrappers, out),
+            TyKind::Array(ty, _) => nested(&**ty, Wrapper::Array, wrappers, out),
+            TyKind::Paren(ty) => ty.type_refs(wrappers, out),
+            TyKind::Ptr(mt) => {
+                out.push(marker("rawptr", self.span, wrappers));
+                let w = Wrapper::Ptr { mutable: mt.mutbl == Mutability::Mut };
+                nested(mt, w, wrappers, out)
+            }
+            TyKind::Rptr(_, mt) => {
+                let w = Wrapper::Ref { mutable: mt.mutbl == Mutability::Mut };
+                nested(mt, w, wrappers, out)
+            }
+            TyKind::BareFn(f) => {
+                out.push(marker("fnptr", self.span, wrappers));
+                f.type_refs(wrappers, out)
+            }
+            TyKind::Tup(tys) => {
+                for (i, ty) in tys.iter().enumerate() {
+                    nested(&**ty, Wrapper::Tuple { index: i }, wrappers, out);
+                }
+            }
+            TyKind::Path(None, p) => p.type_refs(wrappers, out),
+            TyKind::Path(Some(qself), p) => qself_type_refs(qself, p, wrappers, out),
+            TyKind::TraitObject(bounds, _) => {
+                out.push(marker("dyn", self.span, wrappers));
+                bounds_type_refs(bounds, |ty| Wrapper::Dyn { ty }, wrappers, out)
+            }
+            TyKind::ImplTrait(_, bounds) => {
+                bounds_type_refs(bounds, |ty| Wrapper::Impl { ty }, wrappers, out)
+            }
+            TyKind::Typeof(_)
+            | TyKind::MacCall(_)
+            | TyKind::ImplicitSelf
+            | TyKind::Never
+            | TyKind::Infer
+            | TyKind::Err
+            | TyKind::CVarArgs => {}
+        }
+    }
+}
+
+impl ContainTypes for MutTy {
+    fn type_refs(&self, wrappers: &mut Vec<Wrapper>, out: &mut Vec<TypeRef>) {
+        self.ty.type_refs(wrappers, out)
+    }
+}
+
+impl ContainTypes for BareFnTy {
+    fn type_refs(&self, wrappers: &mut Vec<Wrapper>, out: &mut Vec<TypeRef>) {
+        let FnDecl { inputs, output } = self.decl.deref();
+        for (i, p) in inputs.iter().enumerate() {
+            nested(&*p.ty, Wrapper::FnArg { index: i }, wrappers, out);
+        }
+        if let FnRetTy::Ty(ty) = output {
+            nested(&**ty, Wrapper::FnRet, wrappers, out);
+        }
+    }
+}
+
+impl ContainTypes for Path {
+    fn type_refs(&self, wrappers: &mut Vec<Wrapper>, out: &mut Vec<TypeRef>) {
+        let path = segments(self);
+        out.push(TypeRef { path, wrappers: wrappers.clone(), span: self.span, projection: None });
+        let seg = self.segments.last().unwrap();
+        if let Some(args) = &seg.args {
+            args_type_refs(&seg.ident.to_string(), args, wrappers, out);
+        }
+    }
+}
+
+/// Adds the types referenced in the generic arguments `args` of the type or trait `name`
+fn args_type_refs(
+    name: &str,
+    args: &GenericArgs,
+    wrappers: &mut Vec<Wrapper>,
+    out: &mut Vec<TypeRef>,
+) {
+    match args {
+        GenericArgs::AngleBracketed(a) => {
+            let tys = a.args.iter().filter_map(|a| match a {
+                AngleBracketedArg::Arg(GenericArg::Type(ty)) => Some(ty),
+                _ => None,
+            });
+            for (index, ty) in tys.enumerate() {
+                let w = Wrapper::Arg { ty: name.to_string(), index };
+                nested(&**ty, w, wrappers, out);
+            }
+            for a in &a.args {
+                if let AngleBracketedArg::Constraint(c) = a {
+                    if let Some(args) = &c.gen_args {
+                        args_type_refs(&c.ident.to_string(), args, wrappers, out);
+                    }
+                    let w = Wrapper::Assoc { ty: name.to_string(), name: c.ident.to_string() };
+                    match &c.kind {
+                        AssocTyConstraintKind::Equality { ty } => nested(&**ty, w, wrappers, out),
+                        AssocTyConstraintKind::Bound { bounds } => {
+                            wrappers.push(w);
+                            bounds_type_refs(bounds, |ty| Wrapper::Impl { ty }, wrappers, out);
+                            wrappers.pop();
+                        }
+                    }
+                }
+            }
+        }
+        GenericArgs::Parenthesized(a) => {
+            for (index, ty) in a.inputs.iter().enumerate() {
+                let w = Wrapper::Arg { ty: name.to_string(), index };
+                nested(&**ty, w, wrappers, out);
+            }
+            if let FnRetTy::Ty(ty) = &a.output {
+                let w = Wrapper::Assoc { ty: name.to_string(), name: "Output".to_string() };
+                nested(&**ty, w, wrappers, out);
+            }
+        }
+    }
+}
+
+/// Adds the types referenced in the path `path` qualified by `qself`, e.g. `T` and `Iterator` in
+/// `<T as Iterator>::Item`, nested in the projection. Projections on a trait and a self type
+/// written as a path are added as well, to be resolved once impls are known.
+fn qself_type_refs(
+    qself: &QSelf,
+    path: &Path,
+    wrappers: &mut Vec<Wrapper>,
+    out: &mut Vec<TypeRef>,
+) {
+    let (trait_, rest) = path.segments.split_at(qself.position);
+    let name = match rest.first() {
+        Some(seg) => seg.ident.to_string(),
+        None => return,
+    };
+    let trait_path: TypePath = trait_.iter().map(|s| s.ident.to_string()).collect();
+    wrappers.push(Wrapper::Projection { ty: trait_path.last().cloned(), name: name.clone() });
+    qself.ty.type_refs(wrappers, out);
+    if let Some(seg) = trait_.last() {
+        out.push(TypeRef {
+            path: trait_path.clone(),
+            wrappers: wrappers.clone(),
+            span: path.span,
+            projection: None,
+        });
+        if let Some(args) = &seg.args {
+            args_type_refs(&seg.ident.to_string(), args, wrappers, out);
+        }
+    }
+    if let (TyKind::Path(None, p), false, 1) = (&qself.ty.kind, trait_.is_empty(), rest.len()) {
+        out.push(TypeRef {
+            path: vec![name.clone()],
+            wrappers: wrappers.clone(),
+            span: path.span,
+            projection: Some(Projection { self_ty: segments(p), trait_: Some(trait_path), name }),
+        });
+    }
+    wrappers.pop();
+}
+
+/// Adds the traits of `bounds` and the types referenced in their arguments, nested in the
+/// wrapper `erased` makes from the name of each trait
+fn bounds_type_refs(
+    bounds: &[GenericBound],
+    erased: impl Fn(String) -> Wrapper,
+    wrappers: &mut Vec<Wrapper>,
+    out: &mut Vec<TypeRef>,
+) {
+    for b in bounds {
+        if let GenericBound::Trait(poly, _) = b {
+            let path = &poly.trait_ref.path;
+            let name = path.segments.last().unwrap().ident.to_string();
+            nested(path, erased(name), wrappers, out);
+        }
+    }
+}
+
+impl ContainTypes for TyAliasKind {
+    fn type_refs(&self, wrappers: &mut Vec<Wrapper>, out: &mut Vec<TypeRef>) {
+        if let Some(ty) = &self.3 {
+            ty.type_refs(wrappers, out);
+        }
+    }
+}
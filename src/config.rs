@@ -0,0 +1,50 @@
+//! Defaults of the command-line options, read from a file found next to the analyzed source
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+pub const FILE_NAME: &str = "extract-dependency.toml";
+
+/// Options used when not given on the command line. Paths are relative to the file.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub sink: Vec<String>,
+    pub root: Vec<String>,
+    pub exclude: Vec<String>,
+    pub model: Vec<PathBuf>,
+    pub cfg: Vec<String>,
+    pub features: Vec<String>,
+    pub edition: Option<String>,
+    pub policy: Option<PathBuf>,
+    pub baseline: Option<PathBuf>,
+    pub implementations: bool,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config {}", path.display()))?;
+        let mut config: Config =
+            toml::from_str(&s).with_context(|| format!("invalid config {}", path.display()))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for p in &mut config.model {
+            *p = dir.join(&*p);
+        }
+        for p in config.policy.iter_mut().chain(&mut config.baseline) {
+            *p = dir.join(&*p);
+        }
+        Ok(config)
+    }
+}
+
+/// The config file in the directory of `input` or the closest ancestor having one
+pub fn find(input: &Path) -> Option<PathBuf> {
+    let input = input.canonicalize().ok()?;
+    let start = if input.is_dir() { &input } else { input.parent()? };
+    start.ancestors().map(|d| d.join(FILE_NAME)).find(|f| f.is_file())
+}
@@ -0,0 +1,172 @@
+//! Comparison of the graphs of two revisions
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::json::{Edge, Node, Snapshot};
+use crate::output::escape_dot;
+
+/// Changes between two snapshots, restricted to the types from which a sink is reachable and
+/// the edges among them
+pub struct Diff<'a> {
+    /// Types that reach a sink only in the new snapshot
+    pub reachable: Vec<&'a Node>,
+    /// Types that reach a sink only in the old snapshot
+    pub unreachable: Vec<&'a Node>,
+    /// Types that reach sinks in both, but not the same ones, with the old and new nodes
+    pub changed: Vec<(&'a Node, &'a Node)>,
+    pub added: Vec<&'a Edge>,
+    pub removed: Vec<&'a Edge>,
+    pub unchanged: Vec<&'a Edge>,
+}
+
+impl Diff<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.reachable.is_empty()
+            && self.unreachable.is_empty()
+            && self.changed.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+    }
+}
+
+pub fn diff<'a>(old: &'a Snapshot, new: &'a Snapshot) -> Diff<'a> {
+    let old_nodes = reachable(old);
+    let new_nodes = reachable(new);
+    let old_edges = edges(old, &old_nodes);
+    let new_edges = edges(new, &new_nodes);
+
+    let mut diff = Diff {
+        reachable: vec![],
+        unreachable: vec![],
+        changed: vec![],
+        added: vec![],
+        removed: vec![],
+        unchanged: vec![],
+    };
+    for (k, n) in &new_nodes {
+        match old_nodes.get(k) {
+            None => diff.reachable.push(*n),
+            Some(o) if o.sinks != n.sinks => diff.changed.push((*o, *n)),
+            Some(_) => {}
+        }
+    }
+    diff.unreachable =
+        old_nodes.iter().filter(|(k, _)| !new_nodes.contains_key(*k)).map(|(_, n)| *n).collect();
+    for (k, e) in &new_edges {
+        if old_edges.contains_key(k) {
+            diff.unchanged.push(*e);
+        } else {
+            diff.added.push(*e);
+        }
+    }
+    diff.removed =
+        old_edges.iter().filter(|(k, _)| !new_edges.contains_key(*k)).map(|(_, e)| *e).collect();
+    diff
+}
+
+fn reachable(snapshot: &Snapshot) -> BTreeMap<&str, &Node> {
+    snapshot.nodes.iter().filter(|n| n.reachable).map(|n| (n.path.as_str(), n)).collect()
+}
+
+/// Identity of an edge: its source, target, field, variant and wrappers, but not its location,
+/// which changes with unrelated edits
+type EdgeKey<'a> = (&'a str, &'a str, Option<&'a str>, Option<&'a str>, Vec<String>);
+
+fn edges<'a>(
+    snapshot: &'a Snapshot,
+    nodes: &BTreeMap<&str, &Node>,
+) -> BTreeMap<EdgeKey<'a>, &'a Edge> {
+    snapshot
+        .edges
+        .iter()
+        .filter(|e| nodes.contains_key(e.source.as_str()) && nodes.contains_key(e.target.as_str()))
+        .map(|e| {
+            let key = (
+                e.source.as_str(),
+                e.target.as_str(),
+                e.field.as_deref(),
+                e.variant.as_deref(),
+                e.wrappers.iter().map(|w| w.to_string()).collect(),
+            );
+            (key, e)
+        })
+        .collect()
+}
+
+/// Writes the changes, one per line, with `+` for additions and `-` for removals
+pub fn write_text(out: &mut dyn Write, diff: &Diff<'_>) -> io::Result<()> {
+    if diff.is_empty() {
+        return writeln!(out, "no changes");
+    }
+    for n in &diff.reachable {
+        writeln!(out, "+ {} reaches {}", n.path, n.sinks.join(", "))?;
+    }
+    for n in &diff.unreachable {
+        writeln!(out, "- {} reaches {}", n.path, n.sinks.join(", "))?;
+    }
+    for (o, n) in &diff.changed {
+        writeln!(
+            out,
+            "~ {} reaches {} instead of {}",
+            n.path,
+            n.sinks.join(", "),
+            o.sinks.join(", ")
+        )?;
+    }
+    for (sign, es) in &[('+', &diff.added), ('-', &diff.removed)] {
+        for e in es.iter() {
+            writeln!(out, "{} {} -> {} ({})", sign, e.source, e.target, e.edge().reason())?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the union of both graphs in the DOT format, with additions in green and removals in
+/// red
+pub fn write_dot(
+    out: &mut dyn Write,
+    old: &Snapshot,
+    new: &Snapshot,
+    diff: &Diff<'_>,
+) -> io::Result<()> {
+    let added: BTreeSet<&str> = diff.reachable.iter().map(|n| n.path.as_str()).collect();
+    let removed: BTreeSet<&str> = diff.unreachable.iter().map(|n| n.path.as_str()).collect();
+    let mut nodes: BTreeMap<&str, &Node> = reachable(old);
+    nodes.extend(reachable(new));
+
+    out.write_all(b"digraph G {\n")?;
+    for (k, n) in nodes {
+        // types not defined in the source are left implicit, as in the graph
+        if n.kind == "external" {
+            continue;
+        }
+        let color = if added.contains(k) {
+            ", color=green, fontcolor=green"
+        } else if removed.contains(k) {
+            ", color=red, fontcolor=red"
+        } else {
+            ""
+        };
+        writeln!(out, "  \"{}\" [class=\"{}\"{}];", k, n.kind, color)?;
+    }
+    let edges = diff
+        .added
+        .iter()
+        .map(|e| (e, "green"))
+        .chain(diff.removed.iter().map(|e| (e, "red")))
+        .chain(diff.unchanged.iter().map(|e| (e, "gray")));
+    for (e, color) in edges {
+        writeln!(
+            out,
+            "  \"{}\" -> \"{}\" [color={}, label=\"{}\"];",
+            e.source,
+            e.target,
+            color,
+            escape_dot(&e.edge().label())
+        )?;
+    }
+    out.write_all(b"}")?;
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A position in a source file, with 1-based line and column
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
+
+/// A construct of a type expression in which a type is nested
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Wrapper {
+    /// Generic argument of a type or trait, counting type arguments only
+    Arg {
+        ty: String,
+        index: usize,
+    },
+    /// Associated type binding of a trait, e.g. `Output` in `Fn() -> T`
+    Assoc {
+        ty: String,
+        name: String,
+    },
+    Ptr {
+        mutable: bool,
+    },
+    Ref {
+        mutable: bool,
+    },
+    Array,
+    Slice,
+    Tuple {
+        index: usize,
+    },
+    /// Parameter of a function pointer
+    FnArg {
+        index: usize,
+    },
+    /// Return type of a function pointer
+    FnRet,
+    /// Bound of a trait object, whose type is erased
+    Dyn {
+        ty: String,
+    },
+    /// Bound of an `impl Trait` type, whose type is opaque
+    Impl {
+        ty: String,
+    },
+    /// Projection on a self type, e.g. `Item` of `Iterator` in `<T as Iterator>::Item`, around
+    /// its self type and trait or the associated type it resolves to
+    Projection {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ty: Option<String>,
+        name: String,
+    },
+    /// Implementation of a trait by the type, which trait objects of the trait may be
+    Implementation,
+}
+
+impl fmt::Display for Wrapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Wrapper::Arg { ty, index } => write!(f, "inside `{}<_>` arg {}", ty, index),
+            Wrapper::Assoc { ty, name } => write!(f, "inside `{}<{} = _>`", ty, name),
+            Wrapper::Ptr { mutable: true } => write!(f, "behind `*mut`"),
+            Wrapper::Ptr { mutable: false } => write!(f, "behind `*const`"),
+            Wrapper::Ref { mutable: true } => write!(f, "behind `&mut`"),
+            Wrapper::Ref { mutable: false } => write!(f, "behind `&`"),
+            Wrapper::Array => write!(f, "inside `[_; N]`"),
+            Wrapper::Slice => write!(f, "inside `[_]`"),
+            Wrapper::Tuple { index } => write!(f, "inside tuple field {}", index),
+            Wrapper::FnArg { index } => write!(f, "inside `fn` arg {}", index),
+            Wrapper::FnRet => write!(f, "inside `fn` return"),
+            Wrapper::Dyn { ty } => write!(f, "erased in `dyn {}`", ty),
+            Wrapper::Impl { ty } => write!(f, "erased in `impl {}`", ty),
+            Wrapper::Projection { ty: Some(ty), name } => {
+                write!(f, "inside `<_ as {}>::{}`", ty, name)
+            }
+            Wrapper::Projection { ty: None, name } => write!(f, "inside `<_>::{}`", name),
+            Wrapper::Implementation => write!(f, "implementation of the trait"),
+        }
+    }
+}
+
+/// A dependency of a type on another one, through one of its fields
+#[derive(Clone)]
+pub struct Edge {
+    pub target: String,
+    /// Name of the field, or its index in a tuple struct or variant
+    pub field: Option<String>,
+    /// Variant the field belongs to, in an enum
+    pub variant: Option<String>,
+    /// Constructs of the field's type the target is nested in, outermost first
+    pub wrappers: Vec<Wrapper>,
+    /// Location of the target in the field's type, or in the aliased type in a type alias.
+    /// Edges from models have none.
+    pub location: Option<Location>,
+}
+
+impl Edge {
+    /// Whether the target is in the bounds of a trait object or `impl Trait` type, which may
+    /// contain other types than the bounds tell
+    pub fn erased(&self) -> bool {
+        self.wrappers.iter().any(|w| matches!(w, Wrapper::Dyn { .. } | Wrapper::Impl { .. }))
+    }
+
+    /// The reason for the edge without its location, e.g. "field `next` of variant `Cons`,
+    /// inside `Box<_>` arg 0"
+    pub fn label(&self) -> String {
+        if self.wrappers.first() == Some(&Wrapper::Implementation) {
+            return Wrapper::Implementation.to_string();
+        }
+        let mut s = match (&self.field, &self.variant) {
+            (Some(f), Some(v)) => format!("field `{}` of variant `{}`", f, v),
+            (Some(f), None) => format!("field `{}`", f),
+            (None, _) if self.location.is_none() => "model".to_string(),
+            (None, _) => "aliased type".to_string(),
+        };
+        for w in &self.wrappers {
+            s.push_str(&format!(", {}", w));
+        }
+        s
+    }
+
+    /// The reason for the edge, e.g. "field `next` of variant `Cons`, inside `Box<_>` arg 0,
+    /// at src/list.rs:3:15"
+    pub fn reason(&self) -> String {
+        match &self.location {
+            Some(l) => format!("{}, at {}", self.label(), l),
+            None => self.label(),
+        }
+    }
+}
+
+/// Edges from each type, keyed by canonical path
+pub type Graph = BTreeMap<String, Vec<Edge>>;
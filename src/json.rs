@@ -0,0 +1,165 @@
+//! The JSON export of the graph, whose schema is in `schema/graph-v2.schema.json`. Changes
+//! that may break consumers, such as removing or renaming fields or adding values to the enums
+//! of the schema, require bumping `VERSION`. Version 2 added traits, erased edges, and the
+//! wrappers of trait objects, `impl Trait` types, projections and implementations.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::Analysis;
+use crate::graph::{self, Location, Wrapper};
+
+pub const VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub sinks: Vec<Sink>,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Sink {
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub category: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Node {
+    /// Canonical path, or last segment for external types
+    pub path: String,
+    /// `struct`, `enum`, `union`, `type`, `extern type`, `trait`, or `external` for types that
+    /// are not defined in the analyzed source
+    pub kind: String,
+    pub file: Option<String>,
+    pub span: Option<Span>,
+    pub generics: Vec<String>,
+    pub attributes: Vec<String>,
+    pub reachable: bool,
+    /// Sink types the node reaches
+    pub sinks: Vec<String>,
+    /// Categories of the sink types the node reaches
+    pub categories: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    pub field: Option<String>,
+    pub variant: Option<String>,
+    /// Constructs of the field's type the target is nested in, outermost first
+    pub wrappers: Vec<Wrapper>,
+    pub location: Option<Location>,
+    /// Whether the target is in the bounds of a trait object or `impl Trait` type
+    #[serde(default)]
+    pub erased: bool,
+}
+
+impl Edge {
+    /// The edge as in the graph, to describe it
+    pub fn edge(&self) -> graph::Edge {
+        graph::Edge {
+            target: self.target.clone(),
+            field: self.field.clone(),
+            variant: self.variant.clone(),
+            wrappers: self.wrappers.clone(),
+            location: self.location.clone(),
+        }
+    }
+}
+
+/// Reads a snapshot written by `--format json`
+pub fn load(path: &Path) -> Result<Snapshot> {
+    let s = fs::read_to_string(path)
+        .with_context(|| format!("failed to read snapshot {}", path.display()))?;
+    let value: serde_json::Value =
+        serde_json::from_str(&s).with_context(|| format!("invalid snapshot {}", path.display()))?;
+    // checked first, as other versions may not parse. Earlier versions describe a subset of
+    // the current one.
+    let version = value.get("version").and_then(|v| v.as_u64());
+    if !version.map_or(false, |v| (1..=VERSION as u64).contains(&v)) {
+        bail!("{} is not a snapshot of versions 1 to {} of the format", path.display(), VERSION);
+    }
+    serde_json::from_value(value).with_context(|| format!("invalid snapshot {}", path.display()))
+}
+
+pub fn snapshot(analysis: &Analysis) -> Snapshot {
+    let Analysis { collector, graph, sinks, reachable } = analysis;
+
+    let mut names: BTreeSet<&str> = graph.keys().map(|k| k.as_str()).collect();
+    names.extend(graph.values().flatten().map(|e| e.target.as_str()));
+    names.extend(sinks.keys().map(|k| k.as_str()));
+
+    let nodes = names
+        .into_iter()
+        .map(|name| {
+            let reached = reachable.get(name);
+            let mut node = Node {
+                path: name.to_string(),
+                kind: "external".to_string(),
+                file: None,
+                span: None,
+                generics: vec![],
+                attributes: vec![],
+                reachable: reached.is_some(),
+                sinks: reached.into_iter().flatten().cloned().collect(),
+                categories: reached
+                    .into_iter()
+                    .flatten()
+                    .map(|s| sinks[s].clone())
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect(),
+            };
+            if let Some(n) = collector.items.get(name) {
+                node.kind = n.kind.to_string();
+                node.file = n.location.as_ref().map(|l| l.file.clone());
+                node.span = n.location.as_ref().zip(n.end.as_ref()).map(|(s, e)| Span {
+                    start: Position { line: s.line, col: s.col },
+                    end: Position { line: e.line, col: e.col },
+                });
+                node.generics = n.generics.clone();
+                node.attributes = n.attrs.clone();
+            }
+            node
+        })
+        .collect();
+
+    let edges = graph
+        .iter()
+        .flat_map(|(source, es)| {
+            es.iter().map(move |e| Edge {
+                source: source.clone(),
+                target: e.target.clone(),
+                field: e.field.clone(),
+                variant: e.variant.clone(),
+                wrappers: e.wrappers.clone(),
+                location: e.location.clone(),
+                erased: e.erased(),
+            })
+        })
+        .collect();
+
+    let sinks = sinks.iter().map(|(t, c)| Sink { ty: t.clone(), category: c.clone() }).collect();
+
+    Snapshot { version: VERSION, sinks, nodes, edges }
+}
@@ -0,0 +1,258 @@
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use rustc_ast::{
+    attr::first_attr_value_str_by_name, ptr::P, Crate, Inline, Item, ItemKind, ModKind,
+};
+use rustc_session::parse::ParseSess;
+use rustc_span::{fatal_error::FatalErrorMarker, symbol::sym};
+
+use crate::cfg::{self, Cfg};
+
+/// Parses the crate whose root file is `root`, loading the files of out-of-line modules
+/// (`mod foo;`) into the AST the way rustc does. Items configured out by `cfg` are removed,
+/// and the modules among them are not loaded. Errors are emitted to the handler of `sess`, and
+/// modules whose files cannot be loaded are left empty.
+pub fn load_crate(root: &Path, sess: &ParseSess, cfg: &Cfg) -> Option<Crate> {
+    let mut krate = parse_file(root, sess)?;
+    let dir = root.parent().unwrap_or_else(|| Path::new(""));
+    Loader { sess, cfg }.load_items(&mut krate.items, dir, dir, false);
+    cfg::strip_crate(&mut krate, cfg);
+    Some(krate)
+}
+
+/// Parses `path` as a crate, emitting errors to the handler of `sess`. Parsers recover from
+/// most syntax errors, in which case the items that could be parsed are returned.
+pub fn parse_file(path: &Path, sess: &ParseSess) -> Option<Crate> {
+    // read here, as the parser panics when the file cannot be read
+    let src = match fs::read_to_string(path) {
+        Ok(src) => src,
+        Err(e) => {
+            sess.span_diagnostic.err(&format!("failed to read {}: {}", path.display(), e));
+            return None;
+        }
+    };
+    let name = path.to_path_buf().into();
+    // the lexer and the parser raise `FatalError` on some errors, after emitting them
+    let parsed = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut parser = match rustc_parse::maybe_new_parser_from_source_str(sess, name, src) {
+            Ok(parser) => parser,
+            Err(diagnostics) => {
+                for d in &diagnostics {
+                    sess.span_diagnostic.emit_diagnostic(d);
+                }
+                return None;
+            }
+        };
+        match parser.parse_crate_mod() {
+            Ok(krate) => Some(krate),
+            Err(mut e) => {
+                e.emit();
+                None
+            }
+        }
+    }));
+    match parsed {
+        Ok(krate) => krate,
+        Err(payload) if payload.is::<FatalErrorMarker>() => None,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+struct Loader<'a> {
+    sess: &'a ParseSess,
+    cfg: &'a Cfg,
+}
+
+impl Loader<'_> {
+    /// Loads the out-of-line modules among `items`. `file_dir` is the directory of the file
+    /// the items are in, and `mod_dir` the directory in which their submodules are looked up.
+    /// Both differ when the items are in a non-`mod.rs` file or inside an inline module.
+    fn load_items(&self, items: &mut Vec<P<Item>>, file_dir: &Path, mod_dir: &Path, inline: bool) {
+        cfg::retain_active(items, self.cfg, |i| &mut i.attrs);
+        for item in items {
+            let Item { attrs, ident, kind, span, .. } = &mut **item;
+            let kind = match kind {
+                ItemKind::Mod(_, kind) => kind,
+                _ => continue,
+            };
+            let name = ident.to_string();
+            let path_attr = first_attr_value_str_by_name(attrs, sym::path).map(|p| p.to_string());
+            match kind {
+                ModKind::Loaded(items, Inline::Yes, _) => {
+                    let dir = mod_dir.join(path_attr.unwrap_or(name));
+                    self.load_items(items, file_dir, &dir, true);
+                }
+                ModKind::Loaded(_, Inline::No, _) => {}
+                ModKind::Unloaded => {
+                    let (file, dir) = match path_attr {
+                        Some(p) => {
+                            // relative to the file unless inside an inline module, and the
+                            // loaded file then behaves like a `mod.rs` file
+                            let file = if inline { mod_dir.join(p) } else { file_dir.join(p) };
+                            let dir = file.parent().unwrap().to_path_buf();
+                            (file, dir)
+                        }
+                        None => {
+                            let dir = mod_dir.join(&name);
+                            let flat = mod_dir.join(format!("{}.rs", name));
+                            let nested = dir.join("mod.rs");
+                            let found = match (flat.is_file(), nested.is_file()) {
+                                (true, false) => Ok((flat, dir)),
+                                (false, true) => Ok((nested, dir)),
+                                (true, true) => Err(format!(
+                                    "file for module `{}` found at both {} and {}",
+                                    name,
+                                    flat.display(),
+                                    nested.display()
+                                )),
+                                (false, false) => Err(format!(
+                                    "file not found for module `{}`: {} or {}",
+                                    name,
+                                    flat.display(),
+                                    nested.display()
+                                )),
+                            };
+                            match found {
+                                Ok(found) => found,
+                                Err(msg) => {
+                                    self.sess.span_diagnostic.span_err(*span, &msg);
+                                    continue;
+                                }
+                            }
+                        }
+                    };
+                    let krate = match parse_file(&file, self.sess) {
+                        Some(krate) => krate,
+                        None => continue,
+                    };
+                    let mut loaded = krate.items;
+                    self.load_items(&mut loaded, file.parent().unwrap(), &dir, false);
+                    attrs.extend(krate.attrs);
+                    *kind = ModKind::Loaded(loaded, Inline::No, krate.span);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rustc_span::{edition::Edition, source_map::FilePathMapping};
+
+    use super::*;
+
+    /// A new temporary directory named after `name` with `files`, given by relative path and
+    /// contents
+    fn dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "extract-dependency-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, src) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, src).unwrap();
+        }
+        dir
+    }
+
+    /// Loads the crate whose root is `lib.rs` in `dir`, returning the paths of its structs
+    /// and the number of errors
+    fn load(dir: &Path) -> (Vec<String>, usize) {
+        rustc_span::with_session_globals(Edition::Edition2018, || {
+            let sess = ParseSess::new(FilePathMapping::empty());
+            let mut structs = vec![];
+            if let Some(krate) = load_crate(&dir.join("lib.rs"), &sess, &Cfg::default()) {
+                structs_in(&krate.items, "crate", &mut structs);
+            }
+            (structs, sess.span_diagnostic.err_count())
+        })
+    }
+
+    fn structs_in(items: &[P<Item>], module: &str, out: &mut Vec<String>) {
+        for i in items {
+            match &i.kind {
+                ItemKind::Struct(..) => out.push(format!("{}::{}", module, i.ident)),
+                ItemKind::Mod(_, ModKind::Loaded(items, ..)) => {
+                    structs_in(items, &format!("{}::{}", module, i.ident), out)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn lexer_error() {
+        let dir = dir(
+            "lexer-error",
+            &[
+                ("lib.rs", "mod bad; mod good;"),
+                ("bad.rs", "const S: &str = \"abc;\npub struct B;"),
+                ("good.rs", "pub struct G;"),
+            ],
+        );
+        let (structs, errors) = load(&dir);
+        assert_eq!(structs, ["crate::good::G"]);
+        assert!(errors > 0);
+    }
+
+    #[test]
+    fn flat_file() {
+        let dir = dir(
+            "flat-file",
+            &[("lib.rs", "mod a;"), ("a.rs", "struct A; mod b;"), ("a/b.rs", "struct B;")],
+        );
+        assert_eq!(load(&dir), (vec!["crate::a::A".into(), "crate::a::b::B".into()], 0));
+    }
+
+    #[test]
+    fn mod_rs() {
+        let dir = dir(
+            "mod-rs",
+            &[("lib.rs", "mod a;"), ("a/mod.rs", "struct A; mod b;"), ("a/b.rs", "struct B;")],
+        );
+        assert_eq!(load(&dir), (vec!["crate::a::A".into(), "crate::a::b::B".into()], 0));
+    }
+
+    #[test]
+    fn ambiguous_file() {
+        let dir = dir(
+            "ambiguous-file",
+            &[("lib.rs", "mod a;"), ("a.rs", "struct A;"), ("a/mod.rs", "struct B;")],
+        );
+        assert_eq!(load(&dir), (vec![], 1));
+    }
+
+    #[test]
+    fn path_in_non_mod_rs() {
+        let dir = dir(
+            "path-in-non-mod-rs",
+            &[("lib.rs", "mod a;"), ("a.rs", "#[path = \"c.rs\"] mod b;"), ("c.rs", "struct C;")],
+        );
+        assert_eq!(load(&dir), (vec!["crate::a::b::C".into()], 0));
+    }
+
+    #[test]
+    fn path_in_inline() {
+        let dir = dir(
+            "path-in-inline",
+            &[("lib.rs", "mod a { #[path = \"x.rs\"] mod b; }"), ("a/x.rs", "struct X;")],
+        );
+        assert_eq!(load(&dir), (vec!["crate::a::b::X".into()], 0));
+    }
+
+    #[test]
+    fn nested_inline() {
+        let dir = dir(
+            "nested-inline",
+            &[("lib.rs", "mod a { mod b { mod c; } }"), ("a/b/c.rs", "struct C;")],
+        );
+        assert_eq!(load(&dir), (vec!["crate::a::b::c::C".into()], 0));
+    }
+}
@@ -1,250 +1,616 @@
+mod analysis;
+mod baseline;
+mod cargo;
+mod cfg;
+mod collect;
+mod config;
+mod diff;
+mod graph;
+mod json;
+mod loader;
+mod model;
+mod output;
+mod policy;
+mod resolve;
+
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::{self, File};
-use std::ops::Deref;
-use std::path::PathBuf;
-use std::{
-    collections::{HashMap, HashSet},
-    io::Write,
-};
-
-use rustc_ast::{
-    visit::{walk_crate, walk_item, Visitor},
-    AngleBracketedArg, BareFnTy, FieldDef, FnDecl, FnRetTy, GenericArg, GenericArgs, Item,
-    ItemKind, MutTy, ParenthesizedArgs, Path, Ty, TyAliasKind, TyKind, VariantData,
-};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
 use rustc_session::parse::ParseSess;
 use rustc_span::edition::Edition;
+use rustc_span::source_map::FilePathMapping;
 
+use anyhow::{bail, Context};
 use clap::Clap;
 
+use analysis::Analysis;
+use baseline::Baseline;
+use cfg::Cfg;
+use collect::Collector;
+use config::Config;
+use model::Model;
+use output::Format;
+use policy::Policy;
+
 #[derive(Clap)]
 struct Args {
-    input: Vec<PathBuf>,
+    #[clap(subcommand)]
+    command: Command,
 }
 
-fn main() -> std::io::Result<()> {
-    let mut args: Args = Args::parse();
-    assert_eq!(args.input.len(), 2);
-    let out = args.input.pop().unwrap();
-    let dir = args.input.pop().unwrap();
-    let files: Vec<PathBuf> = files(dir, "rs");
+#[derive(Clap)]
+enum Command {
+    /// Write the graph of the types from which a sink is reachable
+    Graph(GraphArgs),
+    /// Show why a type is reachable, with paths from it to the sinks it reaches
+    Explain(ExplainArgs),
+    /// Check that no type reaches a sink forbidden by the rules of a policy, exiting with status
+    /// 1 otherwise
+    Check(CheckArgs),
+    /// Show the changes of reachability and of the edges among reachable types between two
+    /// revisions
+    Diff(DiffArgs),
+    /// List the types matching a pattern, with their kinds and the sinks they reach
+    Query(QueryArgs),
+    /// Show the numbers of types, edges and types reaching each sink
+    Stats(StatsArgs),
+}
 
-    let mut collector = Collector { items: HashMap::new() };
+#[derive(Clap, Clone)]
+struct Options {
+    /// Only analyze the given workspace packages and the libraries they depend on
+    #[clap(long, number_of_values = 1)]
+    package: Vec<String>,
+    /// Only analyze the given targets and the libraries they depend on
+    #[clap(long, number_of_values = 1)]
+    target: Vec<String>,
+    /// Set a configuration option for `#[cfg(...)]`, given as `name` or `name="value"`. The
+    /// options of the host are always set.
+    #[clap(long, number_of_values = 1)]
+    cfg: Vec<String>,
+    /// Enable the given features in addition to the default ones
+    #[clap(long, number_of_values = 1)]
+    features: Vec<String>,
+    /// Enable all features of the workspace packages. Requires a `Cargo.toml` input.
+    #[clap(long)]
+    all_features: bool,
+    /// Do not enable the default features of the workspace packages. Requires a `Cargo.toml`
+    /// input.
+    #[clap(long)]
+    no_default_features: bool,
+    /// Read additional knowledge about types, such as the types external ones contain, from
+    /// the given TOML or JSON model file
+    #[clap(long, number_of_values = 1)]
+    model: Vec<PathBuf>,
+    /// Do not use the bundled model of `core`, `alloc` and `std`
+    #[clap(long)]
+    no_default_model: bool,
+    /// Look for the types that contain the given type, or the types of the given category of
+    /// the model, e.g. `interior-mutability`, `raw-pointer`, `heap`, `refcount`, `fn-pointer`
+    /// or `trait-object`. Defaults to `interior-mutability` and `raw-pointer`.
+    #[clap(long, number_of_values = 1)]
+    sink: Vec<String>,
+    /// Only consider the types reachable from the types matching the given pattern, as in
+    /// models
+    #[clap(long, number_of_values = 1)]
+    root: Vec<String>,
+    /// Leave the types matching the given pattern, as in models, out of the graph
+    #[clap(long, number_of_values = 1)]
+    exclude: Vec<String>,
+    /// Edition to parse the source with, 2015, 2018 or 2021, instead of the editions of the
+    /// packages
+    #[clap(long, parse(try_from_str = parse_edition))]
+    edition: Option<Edition>,
+    /// Make traits depend on the types implementing them in the analyzed source, as trait
+    /// objects of a trait may be any of them
+    #[clap(long)]
+    implementations: bool,
+    /// Fail on any error in the source, such as a syntax error or a missing module file,
+    /// instead of analyzing the rest
+    #[clap(long)]
+    strict: bool,
+    /// Read the defaults of the options from the given file instead of the `extract-dependency.toml`
+    /// found in the directory of the input or its ancestors
+    #[clap(long)]
+    config: Option<PathBuf>,
+    /// Do not read the defaults of the options from a file
+    #[clap(long, conflicts_with = "config")]
+    no_config: bool,
+}
 
-    rustc_span::with_session_globals(Edition::Edition2018, || {
-        let parse_sess = ParseSess::with_silent_emitter();
+fn parse_edition(s: &str) -> Result<Edition, String> {
+    s.parse().map_err(|_| format!("unknown edition `{}`", s))
+}
 
-        for f in files {
-            collector.collect(&f, &parse_sess);
-        }
-    });
+#[derive(Clap)]
+struct GraphArgs {
+    /// Source directory, crate root file or `Cargo.toml` of a workspace
+    input: PathBuf,
+    #[clap(flatten)]
+    options: Options,
+    /// File to write the graph to, instead of the standard output
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// Output format: `dot`, `graphml`, `gexf`, `mermaid`, `html` for an interactive report,
+    /// `json` for the whole graph with the reachable types marked, or `tsv` for one line per edge
+    /// with its field, variant, wrappers and location
+    #[clap(long, default_value = "dot")]
+    format: Format,
+    /// Label edges coming from enum payloads with the variant names
+    #[clap(long)]
+    variant_labels: bool,
+    /// Draw an edge per field, labelled with the field, variant and wrappers
+    #[clap(long)]
+    field_labels: bool,
+    /// Do not group types in DOT clusters by module and crate
+    #[clap(long)]
+    no_clusters: bool,
+    /// Draw the given module and its submodules as a single node, given as a path pattern as in
+    /// models, e.g. `crate::tree` or `my_crate::*`
+    #[clap(long, number_of_values = 1)]
+    collapse: Vec<String>,
+}
+
+#[derive(Clap)]
+struct ExplainArgs {
+    /// Source directory, crate root file or `Cargo.toml` of a workspace
+    input: PathBuf,
+    #[clap(flatten)]
+    options: Options,
+    /// Canonical path or name of the type
+    #[clap(name = "TYPE")]
+    ty: String,
+    /// Show all simple paths instead of a shortest one
+    #[clap(long)]
+    all: bool,
+    /// Maximum number of paths to show per sink with `--all`
+    #[clap(long, default_value = "100")]
+    limit: usize,
+}
+
+#[derive(Clap)]
+struct CheckArgs {
+    /// Source directory, crate root file or `Cargo.toml` of a workspace
+    input: PathBuf,
+    #[clap(flatten)]
+    options: Options,
+    /// TOML or JSON file of the rules to check
+    #[clap(long)]
+    policy: Option<PathBuf>,
+    /// Also write the violations in the SARIF format
+    #[clap(long)]
+    sarif: Option<PathBuf>,
+    /// Accept the violations recorded in the given baseline file, so that only new ones fail
+    #[clap(long)]
+    baseline: Option<PathBuf>,
+    /// Record the current violations in the baseline file instead of checking them
+    #[clap(long)]
+    update_baseline: bool,
+}
+
+#[derive(Clap)]
+struct DiffArgs {
+    /// Old revision: source directory, crate root file, `Cargo.toml` of a workspace, or JSON
+    /// snapshot written by `graph --format json`
+    old: PathBuf,
+    /// New revision, given as the old one
+    new: PathBuf,
+    #[clap(flatten)]
+    options: Options,
+    /// Also write the union of both graphs in the DOT format, with additions in green and
+    /// removals in red
+    #[clap(long)]
+    dot: Option<PathBuf>,
+}
+
+#[derive(Clap)]
+struct QueryArgs {
+    /// Source directory, crate root file or `Cargo.toml` of a workspace
+    input: PathBuf,
+    #[clap(flatten)]
+    options: Options,
+    /// Pattern of the types, as in models
+    #[clap(default_value = "*")]
+    pattern: String,
+    /// Only list the types from which a sink is reachable
+    #[clap(long)]
+    reachable: bool,
+}
 
-    let mut graph = collector.items;
-    graph.remove("Id");
-    let mut s = HashSet::new();
-    s.insert("UnsafeCell".to_string());
-    graph.insert("Cell".to_string(), s);
+#[derive(Clap)]
+struct StatsArgs {
+    /// Source directory, crate root file or `Cargo.toml` of a workspace
+    input: PathBuf,
+    #[clap(flatten)]
+    options: Options,
+}
 
-    let mut g = graph.clone();
-    let mut reachable = HashSet::new();
-    reachable.insert("UnsafeCell".to_string());
-    reachable.insert("rawptr".to_string());
+fn main() {
+    // status 2 as for invalid arguments, so that it differs from the violations of `check`
+    if let Err(e) = run() {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(2);
+    }
+}
 
-    loop {
-        let mut added = vec![];
-        for (k, v) in &g {
-            if !v.is_disjoint(&reachable) {
-                added.push(k.clone());
+fn run() -> anyhow::Result<()> {
+    let args: Args = Args::parse();
+    match args.command {
+        Command::Graph(args) => {
+            let (options, _) = configure(&args.input, &args.options)?;
+            let analysis = analyze(&args.input, &options)?;
+            let mut out: Box<dyn Write> = match &args.output {
+                Some(path) => Box::new(
+                    File::create(path)
+                        .with_context(|| format!("failed to create {}", path.display()))?,
+                ),
+                None => Box::new(io::stdout()),
+            };
+            let options = output::Options {
+                variant_labels: args.variant_labels,
+                field_labels: args.field_labels,
+                clusters: !args.no_clusters,
+                collapse: args.collapse.clone(),
+            };
+            output::write(&mut *out, &args.format, &analysis, &options)?;
+        }
+        Command::Explain(args) => {
+            let (options, _) = configure(&args.input, &args.options)?;
+            let analysis = analyze(&args.input, &options)?;
+            explain(&analysis, &args)?;
+        }
+        Command::Check(args) => {
+            let (mut options, config) = configure(&args.input, &args.options)?;
+            let policy = match args.policy.as_ref().or_else(|| config.policy.as_ref()) {
+                Some(path) => Policy::load(path)?,
+                None => bail!("no policy given with `--policy` or in {}", config::FILE_NAME),
+            };
+            let baseline = args.baseline.as_ref().or_else(|| config.baseline.as_ref());
+            if args.update_baseline && baseline.is_none() {
+                bail!("no baseline to update given with `--baseline` or in {}", config::FILE_NAME);
+            }
+            // the sinks are those of the rules
+            options.sink = policy.sinks();
+            let analysis = analyze(&args.input, &options)?;
+            let mut violations = policy.check(&analysis, &load_model(&options)?)?;
+            if let Some(path) = baseline {
+                if args.update_baseline {
+                    Baseline::new(&policy, &violations).save(path)?;
+                    println!("recorded {} violations in {}", violations.len(), path.display());
+                    return Ok(());
+                }
+                let baseline = Baseline::load(path)?;
+                baseline.accept(&policy, &mut violations);
+                for (rule, ty, sink) in baseline.stale(&policy, &violations) {
+                    println!(
+                        "note[{}]: {} no longer reaches {}, and can be removed from the baseline",
+                        rule, ty, sink
+                    );
+                }
+            }
+            policy.write_report(&mut io::stdout(), &violations)?;
+            if let Some(path) = &args.sarif {
+                policy.write_sarif(&mut File::create(path)?, &violations)?;
+            }
+            if violations.iter().any(|v| !v.accepted) {
+                std::process::exit(1);
+            }
+        }
+        Command::Diff(args) => {
+            let (options, _) = configure(&args.new, &args.options)?;
+            let old = snapshot(&args.old, &options)?;
+            let new = snapshot(&args.new, &options)?;
+            let d = diff::diff(&old, &new);
+            diff::write_text(&mut io::stdout(), &d)?;
+            if let Some(path) = &args.dot {
+                diff::write_dot(&mut File::create(path)?, &old, &new, &d)?;
             }
         }
-        if added.is_empty() {
-            break;
+        Command::Query(args) => {
+            let (options, _) = configure(&args.input, &args.options)?;
+            let analysis = analyze(&args.input, &options)?;
+            query(&analysis, &args);
         }
-        for t in added.drain(..) {
-            g.remove(&t);
-            reachable.insert(t);
+        Command::Stats(args) => {
+            let (options, _) = configure(&args.input, &args.options)?;
+            let analysis = analyze(&args.input, &options)?;
+            stats(&analysis);
         }
     }
+    Ok(())
+}
 
-    let mut file = File::create(out)?;
+/// `options` completed by the config file for `input`, along with the config
+fn configure(input: &Path, options: &Options) -> anyhow::Result<(Options, Config)> {
+    let path = match &options.config {
+        Some(path) => Some(path.clone()),
+        None if options.no_config => None,
+        None => config::find(input),
+    };
+    let config = match path {
+        Some(path) => Config::load(&path)?,
+        None => Config::default(),
+    };
 
-    file.write_all(b"digraph G {\n")?;
-    for r in &reachable {
-        if let Some(ks) = graph.get(r) {
-            for k in ks.intersection(&reachable) {
-                file.write_fmt(format_args!("  \"{}\" -> \"{}\";\n", r, k))?;
-            }
+    let mut options = options.clone();
+    let defaults = [
+        (&mut options.sink, &config.sink),
+        (&mut options.root, &config.root),
+        (&mut options.exclude, &config.exclude),
+        (&mut options.cfg, &config.cfg),
+        (&mut options.features, &config.features),
+    ];
+    for (option, default) in defaults.iter_mut() {
+        if option.is_empty() {
+            **option = default.to_vec();
         }
     }
-    file.write_all(b"}")?;
-
-    Ok(())
+    if options.model.is_empty() {
+        options.model = config.model.clone();
+    }
+    options.implementations |= config.implementations;
+    if let (None, Some(e)) = (options.edition, &config.edition) {
+        options.edition = Some(parse_edition(e).map_err(anyhow::Error::msg)?);
+    }
+    Ok((options, config))
 }
 
-fn files(path: PathBuf, ext: &str) -> Vec<PathBuf> {
-    if path.is_dir() {
-        fs::read_dir(path)
-            .unwrap()
-            .into_iter()
-            .flat_map(|entry| files(entry.unwrap().path(), ext))
-            .collect()
-    } else if path.extension().unwrap() == ext {
-        vec![path]
+/// The snapshot of the graph of `input`, which is analyzed unless it is a JSON snapshot
+fn snapshot(input: &Path, options: &Options) -> anyhow::Result<json::Snapshot> {
+    if input.extension().map_or(false, |e| e == "json") {
+        json::load(input)
     } else {
-        vec![]
+        Ok(json::snapshot(&analyze(input, options)?))
     }
 }
 
-struct Collector {
-    items: HashMap<String, HashSet<String>>,
+fn load_model(options: &Options) -> anyhow::Result<Model> {
+    let mut model = if options.no_default_model { Model::default() } else { Model::std() };
+    for path in &options.model {
+        model.merge(Model::load(path)?);
+    }
+    Ok(model)
 }
 
-impl Collector {
-    fn collect(&mut self, file: &PathBuf, sess: &ParseSess) {
-        let krate = rustc_parse::parse_crate_from_file(&file, &sess).unwrap();
-        walk_crate(self, &krate);
+fn analyze(src: &Path, options: &Options) -> anyhow::Result<Analysis> {
+    let manifest = src.file_name().map_or(false, |f| f == "Cargo.toml");
+    if !src.exists() {
+        bail!("{} does not exist", src.display());
+    }
+    if src.is_file() && !manifest && src.extension().map_or(true, |e| e != "rs") {
+        bail!("{} is not a source directory, a `.rs` file or a `Cargo.toml`", src.display());
     }
-}
 
-impl<'ast> Visitor<'ast> for Collector {
-    fn visit_item(&mut self, item: &'ast Item) {
-        let k = item.ident.to_string();
-        let res = match &item.kind {
-            ItemKind::Struct(variant, _) => self.items.insert(k.clone(), variant.type_names()),
-            ItemKind::TyAlias(kind) => self.items.insert(k.clone(), kind.type_names()),
-            _ => None,
-        };
-        if let Some(v) = res {
-            println!("[DUP] {}: {:?}", k, v);
-        }
+    let mut model = load_model(options)?;
+    model.exclude.extend(options.exclude.iter().cloned());
 
-        walk_item(self, item);
+    let mut sink = options.sink.clone();
+    if sink.is_empty() {
+        sink = vec!["interior-mutability".to_string(), "raw-pointer".to_string()];
     }
-}
+    let sinks = analysis::sinks(&sink, &model)?;
 
-trait ContainTypes {
-    fn type_names(&self) -> HashSet<String>;
-}
+    let mut cfg = Cfg::host();
+    for spec in &options.cfg {
+        cfg.insert_spec(spec)?;
+    }
 
-impl ContainTypes for VariantData {
-    fn type_names(&self) -> HashSet<String> {
-        match self {
-            VariantData::Struct(fs, _) | VariantData::Tuple(fs, _) => {
-                fs.iter().flat_map(|f| f.type_names()).collect()
-            }
-            _ => HashSet::new(),
+    let targets = if manifest {
+        let features = cargo::Features {
+            features: options.features.clone(),
+            all: options.all_features,
+            no_default: options.no_default_features,
+        };
+        cargo::targets(src, &options.package, &options.target, &features)?
+    } else {
+        if options.all_features || options.no_default_features {
+            bail!("`--all-features` and `--no-default-features` require a `Cargo.toml` input");
         }
-    }
-}
+        for f in &options.features {
+            cfg.insert("feature", Some(f.as_str()));
+        }
+        vec![]
+    };
 
-impl ContainTypes for FieldDef {
-    fn type_names(&self) -> HashSet<String> {
-        self.ty.type_names()
-    }
-}
+    let mut collector = Collector::default();
+    let mut errors = 0;
 
-impl ContainTypes for Ty {
-    fn type_names(&self) -> HashSet<String> {
-        match &self.kind {
-            TyKind::Slice(ty) | TyKind::Array(ty, _) | TyKind::Paren(ty) => ty.type_names(),
-            TyKind::Ptr(mt) => {
-                let mut s = mt.type_names();
-                s.insert("rawptr".to_string());
-                s
-            },
-            TyKind::Rptr(_, mt) => mt.type_names(),
-            TyKind::BareFn(f) => f.type_names(),
-            TyKind::Tup(tys) => tys.iter().flat_map(|ty| ty.type_names()).collect(),
-            TyKind::Path(_, p) => p.type_names(),
-            TyKind::ImplTrait(_, _)
-            | TyKind::TraitObject(_, _)
-            | TyKind::Typeof(_)
-            | TyKind::MacCall(_)
-            | TyKind::ImplicitSelf
-            | TyKind::Never
-            | TyKind::Infer
-            | TyKind::Err
-            | TyKind::CVarArgs => HashSet::new(),
+    if !targets.is_empty() {
+        for t in &targets {
+            let edition = match options.edition {
+                Some(edition) => edition,
+                None => parse_edition(&t.edition).map_err(anyhow::Error::msg)?,
+            };
+            let mut cfg = cfg.clone();
+            for f in &t.features {
+                cfg.insert("feature", Some(f.as_str()));
+            }
+            if t.kind == "test" || t.kind == "bench" {
+                cfg.insert("test", None);
+            }
+            errors += parse(edition, |sess| {
+                if let Some(krate) = loader::load_crate(&t.root, sess, &cfg) {
+                    collector.collect_crate(vec![t.crate_name.clone()], &krate, sess);
+                }
+            });
+            collector.externs.insert(t.crate_name.clone(), t.externs.clone());
         }
+    } else {
+        // the edition of the package the source belongs to, if any
+        let edition = match (options.edition, cargo::manifest_edition(src)) {
+            (Some(edition), _) => edition,
+            (None, Some(edition)) => parse_edition(&edition).map_err(anyhow::Error::msg)?,
+            (None, None) => Edition::Edition2018,
+        };
+        errors += parse(edition, |sess| {
+            if src.is_file() {
+                // a crate root such as `src/lib.rs`, whose module tree is followed
+                if let Some(krate) = loader::load_crate(src, sess, &cfg) {
+                    collector.collect_crate(vec!["crate".to_string()], &krate, sess);
+                }
+            } else {
+                for f in files(src.to_path_buf(), "rs", sess) {
+                    collector.collect(src, &f, sess, &cfg);
+                }
+            }
+        });
     }
-}
 
-impl ContainTypes for MutTy {
-    fn type_names(&self) -> HashSet<String> {
-        self.ty.type_names()
+    if errors > 0 {
+        if options.strict {
+            bail!("aborting due to {} previous errors", errors);
+        }
+        eprintln!("warning: the analysis is incomplete due to {} previous errors", errors);
     }
-}
 
-impl ContainTypes for BareFnTy {
-    fn type_names(&self) -> HashSet<String> {
-        let FnDecl { inputs, output } = self.decl.deref();
-        let mut set: HashSet<String> = inputs.iter().flat_map(|p| p.ty.type_names()).collect();
-        for tn in output.type_names() {
-            set.insert(tn);
-        }
-        set
+    let mut graph = resolve::graph(&collector, options.implementations);
+    model.apply(&mut graph);
+
+    let mut reachable = analysis::reachable(&graph, &sinks);
+    if !options.root.is_empty() {
+        analysis::restrict(&graph, &mut reachable, &options.root);
     }
+
+    Ok(Analysis { collector, graph, sinks, reachable })
 }
 
-impl ContainTypes for FnRetTy {
-    fn type_names(&self) -> HashSet<String> {
-        match self {
-            FnRetTy::Ty(ty) => ty.type_names(),
-            _ => HashSet::new(),
+fn explain(analysis: &Analysis, args: &ExplainArgs) -> anyhow::Result<()> {
+    let Analysis { graph, reachable, .. } = analysis;
+    let ty = find_type(analysis, &args.ty)?;
+    let sinks = match reachable.get(ty) {
+        Some(sinks) => sinks,
+        None => {
+            println!("{} does not reach any sink", ty);
+            return Ok(());
+        }
+    };
+    for sink in sinks {
+        println!("{} reaches {}:", ty, sink);
+        let paths = if args.all {
+            analysis::simple_paths(graph, reachable, ty, sink, args.limit)
+        } else {
+            analysis::shortest_path(graph, reachable, ty, sink).into_iter().collect()
+        };
+        for path in paths {
+            println!("  {}", ty);
+            for e in path {
+                println!("  -> {} ({})", e.target, e.reason());
+            }
+            println!();
         }
     }
+    Ok(())
 }
 
-impl ContainTypes for Path {
-    fn type_names(&self) -> HashSet<String> {
-        let seg = self.segments.last().unwrap();
-        let mut set = seg.args.as_ref().map(|a| a.type_names()).unwrap_or(HashSet::new());
-        set.insert(seg.ident.to_string());
-        set
+/// The type `name` designates, given as a canonical path or a pattern as in models
+fn find_type<'a>(analysis: &'a Analysis, name: &str) -> anyhow::Result<&'a str> {
+    let types = analysis.graph.keys().chain(analysis.sinks.keys());
+    if let Some(t) = types.clone().find(|t| *t == name) {
+        return Ok(t.as_str());
+    }
+    let candidates: BTreeSet<&str> =
+        types.filter(|t| model::matches(name, t)).map(|t| t.as_str()).collect();
+    match candidates.len() {
+        0 => bail!("no type matches `{}`", name),
+        1 => Ok(candidates.into_iter().next().unwrap()),
+        _ => bail!(
+            "`{}` is ambiguous, matching {}",
+            name,
+            candidates.into_iter().collect::<Vec<_>>().join(", ")
+        ),
     }
 }
 
-impl ContainTypes for GenericArgs {
-    fn type_names(&self) -> HashSet<String> {
-        match self {
-            GenericArgs::AngleBracketed(a) => a.args.iter().flat_map(|a| a.type_names()).collect(),
-            GenericArgs::Parenthesized(a) => a.type_names(),
+/// Prints the types matching the pattern of `args`, one per line with their kinds and the
+/// sinks they reach
+fn query(analysis: &Analysis, args: &QueryArgs) {
+    let Analysis { collector, graph, sinks, reachable } = analysis;
+    let mut types: BTreeSet<&str> = graph.keys().map(|k| k.as_str()).collect();
+    types.extend(graph.values().flatten().map(|e| e.target.as_str()));
+    types.extend(sinks.keys().map(|k| k.as_str()));
+    for t in types {
+        if !model::matches(&args.pattern, t) {
+            continue;
+        }
+        let reached = reachable.get(t);
+        if args.reachable && reached.is_none() {
+            continue;
         }
+        let kind = collector.items.get(t).map_or("external".to_string(), |n| n.kind.to_string());
+        let reached: Vec<&str> = reached.into_iter().flatten().map(|s| s.as_str()).collect();
+        println!("{}\t{}\t{}", t, kind, reached.join(", "));
     }
 }
 
-impl ContainTypes for AngleBracketedArg {
-    fn type_names(&self) -> HashSet<String> {
-        match self {
-            AngleBracketedArg::Arg(a) => a.type_names(),
-            AngleBracketedArg::Constraint(c) => match &c.gen_args {
-                Some(a) => a.type_names(),
-                None => HashSet::new(),
-            },
-        }
+/// Prints the numbers of types by kind, of edges and of types reaching each sink
+fn stats(analysis: &Analysis) {
+    let Analysis { collector, graph, sinks, reachable } = analysis;
+
+    let mut kinds: BTreeMap<String, usize> = BTreeMap::new();
+    for n in collector.items.values() {
+        *kinds.entry(n.kind.to_string()).or_default() += 1;
     }
-}
+    let kinds: Vec<String> = kinds.iter().map(|(k, n)| format!("{} {}", n, k)).collect();
+    println!("types: {} ({})", collector.items.len(), kinds.join(", "));
+    let external: BTreeSet<&str> = graph
+        .values()
+        .flatten()
+        .map(|e| e.target.as_str())
+        .filter(|t| !collector.items.contains_key(*t))
+        .collect();
+    println!("external types: {}", external.len());
+    println!("edges: {}", graph.values().map(|es| es.len()).sum::<usize>());
 
-impl ContainTypes for GenericArg {
-    fn type_names(&self) -> HashSet<String> {
-        match self {
-            GenericArg::Type(ty) => ty.type_names(),
-            _ => HashSet::new(),
+    let reaching = reachable.keys().filter(|t| !sinks.contains_key(*t)).count();
+    println!("types reaching a sink: {}", reaching);
+    for (sink, category) in sinks {
+        let n = reachable.iter().filter(|(t, ss)| *t != sink && ss.contains(sink)).count();
+        if category == sink {
+            println!("  {}: {}", sink, n);
+        } else {
+            println!("  {} ({}): {}", sink, category, n);
         }
     }
 }
 
-impl ContainTypes for ParenthesizedArgs {
-    fn type_names(&self) -> HashSet<String> {
-        let mut set: HashSet<String> = self.inputs.iter().flat_map(|ty| ty.type_names()).collect();
-        for tn in self.output.type_names() {
-            set.insert(tn);
-        }
-        set
-    }
+/// Runs `f` in a new parsing session for `edition`, returning the number of errors emitted.
+/// Nothing from the session, such as spans or symbols, may outlive it.
+fn parse(edition: Edition, f: impl FnOnce(&ParseSess)) -> usize {
+    rustc_span::with_session_globals(edition, || {
+        let sess = ParseSess::new(FilePathMapping::empty());
+        f(&sess);
+        sess.span_diagnostic.err_count()
+    })
 }
 
-impl ContainTypes for TyAliasKind {
-    fn type_names(&self) -> HashSet<String> {
-        self.3.as_ref().map(|ty| ty.type_names()).unwrap_or(HashSet::new())
+/// Files with the extension `ext` under `path`. Directories that cannot be read are reported to
+/// the handler of `sess` and skipped.
+fn files(path: PathBuf, ext: &str, sess: &ParseSess) -> Vec<PathBuf> {
+    if path.is_dir() {
+        let entries = match fs::read_dir(&path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                sess.span_diagnostic.err(&format!("failed to read {}: {}", path.display(), e));
+                return vec![];
+            }
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| match entry {
+                Ok(entry) => Some(entry.path()),
+                Err(e) => {
+                    sess.span_diagnostic.err(&format!("failed to read {}: {}", path.display(), e));
+                    None
+                }
+            })
+            .collect();
+        // sorted, as the order decides which of duplicate definitions and imports wins
+        paths.sort();
+        paths.into_iter().flat_map(|p| files(p, ext, sess)).collect()
+    } else if path.extension().map_or(false, |e| e == ext) {
+        vec![path]
+    } else {
+        vec![]
     }
 }
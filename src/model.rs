@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::graph::{Edge, Graph};
+use crate::resolve::last_segment;
+
+/// Knowledge about types that is not in the analyzed source, read from a TOML or JSON file
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Model {
+    /// Types contained in a type, usually an external one. Keys and targets are canonical
+    /// paths, or last segments for external types.
+    pub edges: BTreeMap<String, Vec<String>>,
+    /// Patterns of types to leave out of the graph. See `matches`.
+    pub exclude: Vec<String>,
+    /// Named sets of sink types
+    pub categories: BTreeMap<String, Vec<String>>,
+}
+
+const STD: &str = include_str!("../models/std.toml");
+
+impl Model {
+    /// The bundled model of `core`, `alloc` and `std`
+    pub fn std() -> Self {
+        toml::from_str(STD).unwrap()
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("failed to read model {}", path.display()))?;
+        let model = if path.extension().map_or(false, |e| e == "json") {
+            serde_json::from_str(&s).map_err(anyhow::Error::from)
+        } else {
+            toml::from_str(&s).map_err(anyhow::Error::from)
+        };
+        model.with_context(|| format!("invalid model {}", path.display()))
+    }
+
+    pub fn merge(&mut self, other: Model) {
+        for (k, v) in other.edges {
+            self.edges.entry(k).or_default().extend(v);
+        }
+        self.exclude.extend(other.exclude);
+        for (k, v) in other.categories {
+            self.categories.entry(k).or_default().extend(v);
+        }
+    }
+
+    /// Adds the edges of the model to `graph` and removes the excluded types
+    pub fn apply(&self, graph: &mut Graph) {
+        for (k, v) in &self.edges {
+            let edges = v.iter().map(|t| Edge {
+                target: t.clone(),
+                field: None,
+                variant: None,
+                wrappers: vec![],
+                location: None,
+            });
+            graph.entry(k.clone()).or_default().extend(edges);
+        }
+        if self.exclude.is_empty() {
+            return;
+        }
+        *graph = std::mem::take(graph)
+            .into_iter()
+            .filter(|(k, _)| !self.is_excluded(k))
+            .map(|(k, mut edges)| {
+                edges.retain(|e| !self.is_excluded(&e.target));
+                (k, edges)
+            })
+            .collect();
+    }
+
+    fn is_excluded(&self, name: &str) -> bool {
+        self.exclude.iter().any(|p| matches(p, name))
+    }
+}
+
+/// Whether the type `name` matches `pattern`. A pattern containing `::` is matched against the
+/// whole canonical path, and otherwise against its last segment. `*` matches any sequence of
+/// characters, so that `my_crate::generated::*` matches everything under a module.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let name = if pattern.contains("::") { name } else { last_segment(name) };
+    glob(pattern.as_bytes(), name.as_bytes())
+}
+
+/// Whether `s` matches `pattern`, in which `*` matches any sequence of characters
+pub fn glob(pattern: &[u8], s: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => s.is_empty(),
+        Some((b'*', rest)) => (0..=s.len()).any(|i| glob(rest, &s[i..])),
+        Some((c, rest)) => s.first() == Some(c) && glob(rest, &s[1..]),
+    }
+}
@@ -0,0 +1,485 @@
+//! Writers of the analysis result in the supported output formats
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::analysis::{Analysis, Sinks};
+use crate::collect::NodeKind;
+use crate::json;
+use crate::model;
+
+pub enum Format {
+    Dot,
+    Gexf,
+    GraphMl,
+    Html,
+    Json,
+    Mermaid,
+    Tsv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Format::Dot),
+            "gexf" => Ok(Format::Gexf),
+            "graphml" => Ok(Format::GraphMl),
+            "html" => Ok(Format::Html),
+            "json" => Ok(Format::Json),
+            "mermaid" => Ok(Format::Mermaid),
+            "tsv" => Ok(Format::Tsv),
+            _ => Err(format!("unknown format `{}`", s)),
+        }
+    }
+}
+
+/// How the graph is drawn by the formats showing types as nodes
+#[derive(Default)]
+pub struct Options {
+    /// Label edges coming from enum payloads with the variant names
+    pub variant_labels: bool,
+    /// Draw an edge per field, labelled with the field, variant and wrappers
+    pub field_labels: bool,
+    /// Group types in DOT clusters by the modules and crates they are defined in
+    pub clusters: bool,
+    /// Patterns of the modules drawn as single nodes, along with their submodules
+    pub collapse: Vec<String>,
+}
+
+pub fn write(
+    out: &mut dyn Write,
+    format: &Format,
+    analysis: &Analysis,
+    options: &Options,
+) -> io::Result<()> {
+    match format {
+        Format::Dot => write_dot(out, &View::new(analysis, options), options.clusters),
+        Format::Gexf => write_gexf(out, &View::new(analysis, options)),
+        Format::GraphMl => write_graphml(out, &View::new(analysis, options)),
+        Format::Html => write_html(out, analysis),
+        Format::Json => Ok(serde_json::to_writer_pretty(out, &json::snapshot(analysis))?),
+        Format::Mermaid => write_mermaid(out, &View::new(analysis, options)),
+        Format::Tsv => write_tsv(out, analysis),
+    }
+}
+
+/// The drawn graph: the types from which a sink is reachable and the edges among them
+struct View<'a> {
+    nodes: Vec<ViewNode<'a>>,
+    /// Indices of the source and target nodes, with the label of the edge
+    edges: Vec<(usize, usize, Option<String>)>,
+}
+
+struct ViewNode<'a> {
+    /// Identifier of the node in the formats that restrict identifiers, derived from the name
+    /// so that it does not change with other nodes
+    id: String,
+    /// Canonical path of the type, or path of the collapsed module
+    name: String,
+    kind: ViewKind,
+    /// Module the node is drawn in, empty for types not defined in the source
+    module: &'a [String],
+}
+
+#[derive(Clone, Copy)]
+enum ViewKind {
+    Type(NodeKind),
+    /// A type not defined in the source
+    External,
+    /// A collapsed module, with the number of reachable types in it
+    Module(usize),
+}
+
+impl fmt::Display for ViewKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewKind::Type(k) => write!(f, "{}", k),
+            ViewKind::External => write!(f, "external"),
+            ViewKind::Module(_) => write!(f, "module"),
+        }
+    }
+}
+
+impl<'a> View<'a> {
+    fn new(analysis: &'a Analysis, options: &Options) -> Self {
+        let Analysis { collector, graph, reachable, .. } = analysis;
+
+        let mut nodes: Vec<ViewNode<'_>> = vec![];
+        let mut index: HashMap<String, usize> = HashMap::new();
+        // the node each reachable type is drawn as
+        let mut group: HashMap<&str, usize> = HashMap::new();
+        for r in reachable.keys() {
+            let node = collector.items.get(r);
+            let module = node.map_or(&[][..], |n| &n.module[..]);
+            let collapsed = (1..=module.len()).find(|&i| {
+                options.collapse.iter().any(|p| model::matches(p, &module[..i].join("::")))
+            });
+            let (name, kind, module) = match (collapsed, node) {
+                (Some(i), _) => (module[..i].join("::"), ViewKind::Module(0), &module[..i - 1]),
+                (None, Some(n)) => (r.clone(), ViewKind::Type(n.kind), module),
+                (None, None) => (r.clone(), ViewKind::External, module),
+            };
+            let i = *index.entry(name.clone()).or_insert_with(|| {
+                nodes.push(ViewNode { id: id(&name), name, kind, module });
+                nodes.len() - 1
+            });
+            if let ViewKind::Module(n) = &mut nodes[i].kind {
+                *n += 1;
+            }
+            group.insert(r, i);
+        }
+
+        let mut edges = vec![];
+        // edges from or to collapsed modules, with the number of edges they stand for
+        let mut merged: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+        for r in reachable.keys() {
+            let i = group[r.as_str()];
+            let from_module = matches!(nodes[i].kind, ViewKind::Module(_));
+
+            // edges to the same type through several fields are drawn once
+            let mut targets: BTreeMap<usize, BTreeSet<&str>> = BTreeMap::new();
+            for e in graph.get(r).into_iter().flatten() {
+                let j = match group.get(e.target.as_str()) {
+                    Some(j) => *j,
+                    None => continue,
+                };
+                if from_module || matches!(nodes[j].kind, ViewKind::Module(_)) {
+                    if i != j {
+                        *merged.entry((i, j)).or_default() += 1;
+                    }
+                } else if options.field_labels {
+                    edges.push((i, j, Some(e.label())));
+                } else {
+                    let vs = targets.entry(j).or_default();
+                    vs.extend(e.variant.as_deref());
+                }
+            }
+            for (j, vs) in targets {
+                let label = if options.variant_labels && !vs.is_empty() {
+                    Some(vs.into_iter().collect::<Vec<_>>().join(", "))
+                } else {
+                    None
+                };
+                edges.push((i, j, label));
+            }
+        }
+        for ((i, j), n) in merged {
+            edges.push((i, j, if n > 1 { Some(format!("{} edges", n)) } else { None }));
+        }
+
+        View { nodes, edges }
+    }
+}
+
+/// An identifier made of ASCII alphanumerics and underscores, distinct for distinct names
+fn id(name: &str) -> String {
+    let mut id = "n".to_string();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            id.push(c);
+        } else {
+            id.push_str(&format!("_{:x}_", c as u32));
+        }
+    }
+    id
+}
+
+impl ViewNode<'_> {
+    /// The text shown for the node
+    fn label(&self) -> String {
+        match self.kind {
+            ViewKind::Module(1) => format!("{} (1 type)", self.name),
+            ViewKind::Module(n) => format!("{} ({} types)", self.name, n),
+            _ => self.name.clone(),
+        }
+    }
+}
+
+/// Nodes of a module of the DOT output, and its submodules
+#[derive(Default)]
+struct Cluster<'a> {
+    nodes: Vec<usize>,
+    children: BTreeMap<&'a str, Cluster<'a>>,
+}
+
+fn write_dot(out: &mut dyn Write, view: &View<'_>, clusters: bool) -> io::Result<()> {
+    out.write_all(b"digraph G {\n")?;
+    let mut root = Cluster::default();
+    for (i, n) in view.nodes.iter().enumerate() {
+        // types not defined in the source are left implicit
+        if let ViewKind::External = n.kind {
+            continue;
+        }
+        let mut cluster = &mut root;
+        if clusters {
+            for m in n.module {
+                cluster = cluster.children.entry(m).or_default();
+            }
+        }
+        cluster.nodes.push(i);
+    }
+    write_cluster(out, view, &root, &mut vec![])?;
+    for (i, j, label) in &view.edges {
+        let (s, t) = (&view.nodes[*i].name, &view.nodes[*j].name);
+        match label {
+            Some(l) => writeln!(out, "  \"{}\" -> \"{}\" [label=\"{}\"];", s, t, escape_dot(l))?,
+            None => writeln!(out, "  \"{}\" -> \"{}\";", s, t)?,
+        }
+    }
+    out.write_all(b"}")?;
+
+    Ok(())
+}
+
+fn write_cluster<'a>(
+    out: &mut dyn Write,
+    view: &View<'_>,
+    cluster: &Cluster<'a>,
+    path: &mut Vec<&'a str>,
+) -> io::Result<()> {
+    let indent = "  ".repeat(path.len() + 1);
+    for i in &cluster.nodes {
+        let n = &view.nodes[*i];
+        let style = match n.kind {
+            ViewKind::Type(k) => k.dot_style(),
+            ViewKind::Module(_) => ", shape=folder",
+            ViewKind::External => "",
+        };
+        writeln!(
+            out,
+            "{}\"{}\" [class=\"{}\", label=\"{}\"{}];",
+            indent,
+            n.name,
+            n.kind,
+            escape_dot(&n.label()),
+            style
+        )?;
+    }
+    for (m, c) in &cluster.children {
+        path.push(*m);
+        let name = path.join("::");
+        writeln!(out, "{}subgraph \"cluster_{}\" {{", indent, name)?;
+        writeln!(out, "{}  label=\"{}\";", indent, name)?;
+        write_cluster(out, view, c, path)?;
+        writeln!(out, "{}}}", indent)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+pub fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn write_graphml(out: &mut dyn Write, view: &View<'_>) -> io::Result<()> {
+    out.write_all(
+        b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+          <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n  \
+          <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n  \
+          <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n  \
+          <key id=\"label\" for=\"edge\" attr.name=\"label\" attr.type=\"string\"/>\n  \
+          <graph id=\"G\" edgedefault=\"directed\">\n",
+    )?;
+    for n in &view.nodes {
+        writeln!(out, "    <node id=\"{}\">", n.id)?;
+        writeln!(out, "      <data key=\"name\">{}</data>", escape_xml(&n.label()))?;
+        writeln!(out, "      <data key=\"kind\">{}</data>", n.kind)?;
+        writeln!(out, "    </node>")?;
+    }
+    for (i, j, label) in &view.edges {
+        let (s, t) = (&view.nodes[*i].id, &view.nodes[*j].id);
+        match label {
+            Some(l) => {
+                writeln!(out, "    <edge source=\"{}\" target=\"{}\">", s, t)?;
+                writeln!(out, "      <data key=\"label\">{}</data>", escape_xml(l))?;
+                writeln!(out, "    </edge>")?;
+            }
+            None => writeln!(out, "    <edge source=\"{}\" target=\"{}\"/>", s, t)?,
+        }
+    }
+    out.write_all(b"  </graph>\n</graphml>\n")?;
+
+    Ok(())
+}
+
+fn write_gexf(out: &mut dyn Write, view: &View<'_>) -> io::Result<()> {
+    out.write_all(
+        b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+          <gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n  \
+          <graph defaultedgetype=\"directed\">\n    \
+          <attributes class=\"node\">\n      \
+          <attribute id=\"kind\" title=\"kind\" type=\"string\"/>\n    \
+          </attributes>\n    \
+          <nodes>\n",
+    )?;
+    for n in &view.nodes {
+        writeln!(out, "      <node id=\"{}\" label=\"{}\">", n.id, escape_xml(&n.label()))?;
+        writeln!(
+            out,
+            "        <attvalues><attvalue for=\"kind\" value=\"{}\"/></attvalues>",
+            n.kind
+        )?;
+        writeln!(out, "      </node>")?;
+    }
+    out.write_all(b"    </nodes>\n    <edges>\n")?;
+    // edges between the same nodes are numbered in order
+    let mut counts: HashMap<(usize, usize), usize> = HashMap::new();
+    for (i, j, label) in &view.edges {
+        let (s, t) = (&view.nodes[*i].id, &view.nodes[*j].id);
+        let k = counts.entry((*i, *j)).or_default();
+        *k += 1;
+        let label = label.as_ref().map(|l| format!(" label=\"{}\"", escape_xml(l)));
+        writeln!(
+            out,
+            "      <edge id=\"{}-{}-{}\" source=\"{}\" target=\"{}\"{}/>",
+            s,
+            t,
+            k,
+            s,
+            t,
+            label.unwrap_or_default()
+        )?;
+    }
+    out.write_all(b"    </edges>\n  </graph>\n</gexf>\n")?;
+
+    Ok(())
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Writes a Mermaid flowchart, which can be embedded in Markdown as a `mermaid` code block
+fn write_mermaid(out: &mut dyn Write, view: &View<'_>) -> io::Result<()> {
+    out.write_all(b"graph TD\n")?;
+    for n in &view.nodes {
+        let label = escape_mermaid(&n.label());
+        match n.kind {
+            ViewKind::Type(NodeKind::Struct) => writeln!(out, "  {}[\"{}\"]", n.id, label)?,
+            ViewKind::Type(NodeKind::Enum) => writeln!(out, "  {}{{{{\"{}\"}}}}", n.id, label)?,
+            ViewKind::Type(NodeKind::Union) => writeln!(out, "  {}[/\"{}\"/]", n.id, label)?,
+            ViewKind::Type(NodeKind::TyAlias) => writeln!(out, "  {}([\"{}\"])", n.id, label)?,
+            ViewKind::Type(NodeKind::ForeignType) => writeln!(out, "  {}[[\"{}\"]]", n.id, label)?,
+            ViewKind::Type(NodeKind::Trait) => writeln!(out, "  {}[/\"{}\"\\]", n.id, label)?,
+            ViewKind::External => writeln!(out, "  {}((\"{}\"))", n.id, label)?,
+            ViewKind::Module(_) => writeln!(out, "  {}>\"{}\"]", n.id, label)?,
+        }
+    }
+    for (i, j, label) in &view.edges {
+        let (s, t) = (&view.nodes[*i].id, &view.nodes[*j].id);
+        match label {
+            Some(l) => writeln!(out, "  {} -->|\"{}\"| {}", s, escape_mermaid(l), t)?,
+            None => writeln!(out, "  {} --> {}", s, t)?,
+        }
+    }
+    for n in &view.nodes {
+        let class = n.kind.to_string().replace(' ', "-");
+        writeln!(out, "  class {} {}", n.id, class)?;
+    }
+
+    Ok(())
+}
+
+fn escape_mermaid(s: &str) -> String {
+    s.replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;")
+}
+
+/// The page of the HTML report, in which the data replaces the `/*DATA*/` placeholder
+const REPORT: &str = include_str!("../assets/report.html");
+
+#[derive(Serialize)]
+struct Report<'a> {
+    nodes: Vec<ReportNode<'a>>,
+    edges: Vec<ReportEdge<'a>>,
+    sinks: &'a Sinks,
+}
+
+#[derive(Serialize)]
+struct ReportNode<'a> {
+    path: &'a str,
+    kind: String,
+    /// Module of the type, `None` for types not defined in the source
+    module: Option<String>,
+    location: Option<String>,
+    sinks: &'a BTreeSet<String>,
+}
+
+#[derive(Serialize)]
+struct ReportEdge<'a> {
+    source: &'a str,
+    target: &'a str,
+    label: String,
+    reason: String,
+}
+
+/// Writes a single HTML file, with no external resource, showing the types from which a sink is
+/// reachable and the reasons of the edges among them
+fn write_html(out: &mut dyn Write, analysis: &Analysis) -> io::Result<()> {
+    let Analysis { collector, graph, sinks, reachable } = analysis;
+
+    let nodes = reachable
+        .iter()
+        .map(|(r, s)| {
+            let node = collector.items.get(r);
+            ReportNode {
+                path: r,
+                kind: node.map_or(ViewKind::External, |n| ViewKind::Type(n.kind)).to_string(),
+                module: node.map(|n| n.module.join("::")),
+                location: node.and_then(|n| n.location.as_ref()).map(|l| l.to_string()),
+                sinks: s,
+            }
+        })
+        .collect();
+    let edges = reachable
+        .keys()
+        .flat_map(|r| graph.get(r).into_iter().flatten().map(move |e| (r, e)))
+        .filter(|(_, e)| reachable.contains_key(&e.target))
+        .map(|(r, e)| ReportEdge {
+            source: r,
+            target: &e.target,
+            label: e.label(),
+            reason: e.reason(),
+        })
+        .collect();
+
+    // `</` would end the script element the data is embedded in
+    let data = serde_json::to_string(&Report { nodes, edges, sinks })?.replace("</", "<\\/");
+    out.write_all(REPORT.replacen("/*DATA*/", &data, 1).as_bytes())
+}
+
+/// Writes the edges among reachable types, one per line
+fn write_tsv(out: &mut dyn Write, analysis: &Analysis) -> io::Result<()> {
+    let Analysis { graph, reachable, .. } = analysis;
+
+    out.write_all(b"source\ttarget\tfield\tvariant\twrappers\tlocation\n")?;
+    for r in reachable.keys() {
+        for e in graph.get(r).into_iter().flatten() {
+            if !reachable.contains_key(&e.target) {
+                continue;
+            }
+            let wrappers: Vec<String> = e.wrappers.iter().map(|w| w.to_string()).collect();
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                r,
+                e.target,
+                e.field.as_deref().unwrap_or(""),
+                e.variant.as_deref().unwrap_or(""),
+                wrappers.join(", "),
+                e.location.as_ref().map(|l| l.to_string()).unwrap_or_default()
+            )?;
+        }
+    }
+
+    Ok(())
+}
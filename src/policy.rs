@@ -0,0 +1,200 @@
+//! Rules forbidding types to reach some sinks, checked to enforce invariants in CI
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::analysis::{self, Analysis};
+use crate::graph::{Edge, Location};
+use crate::model::{self, Model};
+
+/// Rules read from a TOML or JSON file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub id: String,
+    /// Explanation shown along with violations
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Patterns of the types the rule applies to, as in models. The rule applies to all the
+    /// types defined in the source if empty.
+    #[serde(default)]
+    pub types: Vec<String>,
+    /// Patterns of attributes, e.g. `derive(*Serialize*)`, restricting the rule to the types
+    /// with one of them
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    /// Sink types or categories the types must not reach, as given to `--sink`
+    pub forbid: Vec<String>,
+}
+
+/// A type reaching a sink its rule forbids
+pub struct Violation {
+    /// Index of the rule in the policy
+    pub rule: usize,
+    pub ty: String,
+    pub sink: String,
+    pub location: Option<Location>,
+    /// A shortest path of edges from the type to the sink
+    pub path: Vec<Edge>,
+    /// Whether the violation is accepted by the baseline
+    pub accepted: bool,
+}
+
+impl Policy {
+    pub fn load(path: &Path) -> Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("failed to read policy {}", path.display()))?;
+        let policy = if path.extension().map_or(false, |e| e == "json") {
+            serde_json::from_str(&s).map_err(anyhow::Error::from)
+        } else {
+            toml::from_str(&s).map_err(anyhow::Error::from)
+        };
+        policy.with_context(|| format!("invalid policy {}", path.display()))
+    }
+
+    /// Sinks forbidden by any rule, to analyze
+    pub fn sinks(&self) -> Vec<String> {
+        let sinks: BTreeSet<&String> = self.rules.iter().flat_map(|r| &r.forbid).collect();
+        sinks.into_iter().cloned().collect()
+    }
+
+    pub fn check(&self, analysis: &Analysis, model: &Model) -> Result<Vec<Violation>> {
+        let Analysis { collector, graph, reachable, .. } = analysis;
+        let mut violations = vec![];
+        for (i, rule) in self.rules.iter().enumerate() {
+            let forbidden = analysis::sinks(&rule.forbid, model)
+                .with_context(|| format!("invalid rule `{}`", rule.id))?;
+            for (ty, node) in &collector.items {
+                if !rule.types.is_empty() && !rule.types.iter().any(|p| model::matches(p, ty)) {
+                    continue;
+                }
+                if !rule.attributes.is_empty()
+                    && !node.attrs.iter().any(|a| {
+                        rule.attributes.iter().any(|p| model::glob(p.as_bytes(), a.as_bytes()))
+                    })
+                {
+                    continue;
+                }
+                for sink in reachable.get(ty).into_iter().flatten() {
+                    if sink == ty || !forbidden.contains_key(sink) {
+                        continue;
+                    }
+                    let path = analysis::shortest_path(graph, reachable, ty, sink)
+                        .into_iter()
+                        .flatten()
+                        .cloned()
+                        .collect();
+                    violations.push(Violation {
+                        rule: i,
+                        ty: ty.clone(),
+                        sink: sink.clone(),
+                        location: node.location.clone(),
+                        path,
+                        accepted: false,
+                    });
+                }
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Writes the violations not accepted by the baseline, with the paths through which the
+    /// types reach the sinks
+    pub fn write_report(&self, out: &mut dyn Write, violations: &[Violation]) -> io::Result<()> {
+        for v in violations.iter().filter(|v| !v.accepted) {
+            let rule = &self.rules[v.rule];
+            writeln!(out, "error[{}]: {} reaches {}", rule.id, v.ty, v.sink)?;
+            if let Some(l) = &v.location {
+                writeln!(out, "  --> {}", l)?;
+            }
+            if let Some(d) = &rule.description {
+                writeln!(out, "  = {}", d)?;
+            }
+            writeln!(out, "  {}", v.ty)?;
+            for e in &v.path {
+                writeln!(out, "  -> {} ({})", e.target, e.reason())?;
+            }
+            writeln!(out)?;
+        }
+        let new: Vec<&Violation> = violations.iter().filter(|v| !v.accepted).collect();
+        let types: BTreeSet<&str> = new.iter().map(|v| v.ty.as_str()).collect();
+        write!(out, "{} violations by {} types", new.len(), types.len())?;
+        match violations.len() - new.len() {
+            0 => writeln!(out),
+            n => writeln!(out, ", and {} accepted by the baseline", n),
+        }
+    }
+
+    /// Writes the violations in the SARIF 2.1.0 format, read by code scanning tools
+    pub fn write_sarif(&self, out: &mut dyn Write, violations: &[Violation]) -> io::Result<()> {
+        let rules: Vec<_> = self
+            .rules
+            .iter()
+            .map(|r| {
+                let text = r
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| format!("types must not reach {}", r.forbid.join(", ")));
+                json!({ "id": r.id, "shortDescription": { "text": text } })
+            })
+            .collect();
+        let results: Vec<_> = violations
+            .iter()
+            .map(|v| {
+                let mut message = format!("{} reaches {}", v.ty, v.sink);
+                for e in &v.path {
+                    message.push_str(&format!("\n-> {} ({})", e.target, e.reason()));
+                }
+                let locations: Vec<_> = v
+                    .location
+                    .iter()
+                    .map(|l| {
+                        json!({
+                            "physicalLocation": {
+                                "artifactLocation": { "uri": l.file },
+                                "region": { "startLine": l.line, "startColumn": l.col }
+                            }
+                        })
+                    })
+                    .collect();
+                json!({
+                    "ruleId": self.rules[v.rule].id,
+                    "ruleIndex": v.rule,
+                    "level": "error",
+                    "message": { "text": message },
+                    "locations": locations,
+                    "baselineState": if v.accepted { "unchanged" } else { "new" },
+                    "partialFingerprints": { "type/v1": format!("{}|{}", v.ty, v.sink) }
+                })
+            })
+            .collect();
+        let sarif = json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules
+                    }
+                },
+                "results": results
+            }]
+        });
+        Ok(serde_json::to_writer_pretty(out, &sarif)?)
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::{HashMap, HashSet};
+use std::ptr;
+
+use crate::collect::{AssocItem, AssocKind, Collector, Impl, ImportKind, Ref};
+use crate::graph::{Edge, Graph, Wrapper};
+
+/// What a name in a module scope refers to
+#[derive(Clone, PartialEq, Eq)]
+enum Binding {
+    /// A collected definition or a module, by canonical path
+    Def(String),
+    /// Something outside the crate, by its last segment
+    Extern(String),
+}
+
+/// Resolves type paths written inside crate modules to the canonical paths of the collected
+/// definitions, taking `use` declarations into account
+pub struct Resolver<'a> {
+    collector: &'a Collector,
+    /// Names visible in each module, keyed by the canonical path of the module
+    scopes: HashMap<String, HashMap<String, Binding>>,
+    /// Impl blocks and trait definitions, keyed by the canonical path of the self type or of
+    /// the defined trait, along with that of their trait
+    impls: HashMap<String, Vec<(Option<String>, &'a Impl)>>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(collector: &'a Collector) -> Self {
+        let mut scopes: HashMap<String, HashMap<String, Binding>> = HashMap::new();
+        for m in &collector.modules {
+            scopes.entry(m.clone()).or_default();
+            if let Some((parent, name)) = m.rsplit_once("::") {
+                let scope = scopes.entry(parent.to_string()).or_default();
+                scope.insert(name.to_string(), Binding::Def(m.clone()));
+            }
+        }
+        for (k, node) in &collector.items {
+            let scope = scopes.entry(node.module.join("::")).or_default();
+            scope.insert(last_segment(k).to_string(), Binding::Def(k.clone()));
+        }
+
+        let mut resolver = Self { collector, scopes, impls: HashMap::new() };
+        resolver.resolve_imports();
+        for i in &collector.impls {
+            let trait_ = i.trait_.as_ref().map(|t| resolver.resolve(&i.module, t));
+            let ty = match (&i.self_ty, &trait_) {
+                (Some(ty), _) => resolver.resolve(&i.module, ty),
+                (None, Some(t)) => t.clone(),
+                (None, None) => continue,
+            };
+            resolver.impls.entry(ty).or_default().push((trait_, i));
+        }
+        resolver
+    }
+
+    /// Adds the bindings introduced by `use` declarations. An import may depend on names
+    /// introduced by other imports, in particular glob ones, so this iterates until nothing
+    /// changes; imports that still cannot be resolved then are taken to be external. Names are
+    /// only bound once, except that a single import shadows a glob one, so that this ends even
+    /// when imports conflict.
+    fn resolve_imports(&mut self) {
+        let collector = self.collector;
+        // names bound by glob imports, by module
+        let mut globbed: HashSet<(String, String)> = HashSet::new();
+        loop {
+            let mut changed = false;
+            for import in &collector.imports {
+                let module = import.module.join("::");
+                let binding = match self.lookup(&import.module, &import.path) {
+                    Some(b) => b,
+                    None => continue,
+                };
+                match &import.kind {
+                    ImportKind::Single(name) => {
+                        let scope = self.scopes.entry(module.clone()).or_default();
+                        let key = (module, name.clone());
+                        if !scope.contains_key(name) || globbed.remove(&key) {
+                            scope.insert(name.clone(), binding);
+                            changed = true;
+                        }
+                    }
+                    ImportKind::Glob => {
+                        let target = match binding {
+                            Binding::Def(m) if m != module => m,
+                            _ => continue,
+                        };
+                        let names = self.scopes.get(&target).cloned().unwrap_or_default();
+                        let scope = self.scopes.entry(module.clone()).or_default();
+                        for (name, b) in names {
+                            if !scope.contains_key(&name) {
+                                globbed.insert((module.clone(), name.clone()));
+                                scope.insert(name, b);
+                                changed = true;
+                            }
+                        }
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        for import in &collector.imports {
+            if let (ImportKind::Single(name), Some(last)) = (&import.kind, import.path.last()) {
+                let scope = self.scopes.entry(import.module.join("::")).or_default();
+                if !scope.contains_key(name) {
+                    scope.insert(name.clone(), Binding::Extern(last.clone()));
+                }
+            }
+        }
+    }
+
+    /// Canonical path of `path` used in `module`. Paths that do not name a collected
+    /// definition, e.g. those of external types, prelude types or generic parameters, resolve
+    /// to their last segment, even when a definition of the crate has the same name.
+    pub fn resolve(&self, module: &[String], path: &[String]) -> String {
+        match self.lookup(module, path) {
+            Some(Binding::Def(k)) if self.collector.items.contains_key(&k) => k,
+            _ => path.last().unwrap().clone(),
+        }
+    }
+
+    /// Adds the targets of the reference `r` written in `module` to `out`, with the wrappers
+    /// they are nested in. A projection stands for the types its associated type is defined
+    /// as, when the impl defining it is collected, and adds nothing otherwise as its self type
+    /// and trait are referenced on their own. `stack` holds the associated types being expanded.
+    fn targets(
+        &self,
+        module: &[String],
+        r: &'a Ref,
+        stack: &mut Vec<&'a AssocItem>,
+        out: &mut Vec<(String, Vec<Wrapper>)>,
+    ) {
+        let p = match &r.projection {
+            Some(p) => p,
+            None => {
+                out.push((self.resolve(module, &r.path), r.wrappers.clone()));
+                return;
+            }
+        };
+        let self_ty = self.resolve(module, &p.self_ty);
+        let trait_ = p.trait_.as_ref().map(|t| self.resolve(module, t));
+        let (assoc_module, assoc) = match self.assoc_type(&self_ty, trait_.as_deref(), &p.name) {
+            Some((m, a)) if !stack.iter().any(|s| ptr::eq(*s, a)) => (m, a),
+            _ => return,
+        };
+        stack.push(assoc);
+        for a in &assoc.refs {
+            let start = out.len();
+            self.targets(assoc_module, a, stack, out);
+            for (_, wrappers) in &mut out[start..] {
+                wrappers.splice(0..0, r.wrappers.iter().cloned());
+            }
+        }
+        stack.pop();
+    }
+
+    /// The associated type `name` in the impl of `trait_` for `ty`, or in the definition of
+    /// `trait_` when the impl does not define it, with the module it is defined in. Without
+    /// `trait_`, any trait `ty` implements is looked at.
+    fn assoc_type(
+        &self,
+        ty: &str,
+        trait_: Option<&str>,
+        name: &str,
+    ) -> Option<(&'a [String], &'a AssocItem)> {
+        let find = |ty: &str, trait_: Option<&str>, definition: bool| {
+            let impls = self.impls.get(ty)?.iter().filter(|(t, i)| {
+                t.is_some()
+                    && (trait_.is_none() || t.as_deref() == trait_)
+                    && i.self_ty.is_none() == definition
+            });
+            let mut items =
+                impls.flat_map(|&(_, i)| i.items.iter().map(move |a| (&i.module[..], a)));
+            items.find(|(_, a)| a.kind == AssocKind::Type && a.name == name)
+        };
+        find(ty, trait_, false).or_else(|| match trait_ {
+            Some(t) => find(t, Some(t), true),
+            None => {
+                let mut traits = self.impls.get(ty)?.iter().filter_map(|(t, _)| t.as_deref());
+                traits.find_map(|t| find(t, Some(t), true))
+            }
+        })
+    }
+
+    fn lookup(&self, module: &[String], path: &[String]) -> Option<Binding> {
+        let (first, rest) = path.split_first()?;
+        let mut current = match first.as_str() {
+            "crate" => Binding::Def(module[0].clone()),
+            "self" => Binding::Def(module.join("::")),
+            "super" => {
+                let supers = path.iter().take_while(|s| s.as_str() == "super").count();
+                let len = module.len().saturating_sub(supers).max(1);
+                return self.lookup_in(Binding::Def(module[..len].join("::")), &path[supers..]);
+            }
+            "{{root}}" => {
+                let (name, rest) = rest.split_first()?;
+                return self.lookup_in(self.lookup_extern(module, name)?, rest);
+            }
+            name => match self.scopes.get(&module.join("::")).and_then(|s| s.get(name)) {
+                Some(b) => b.clone(),
+                None => self.lookup_extern(module, name)?,
+            },
+        };
+        for seg in rest {
+            current = self.lookup_in(current, std::slice::from_ref(seg))?;
+        }
+        Some(current)
+    }
+
+    /// Root module of the crate `name` refers to in `module`, when it is one of the analyzed
+    /// crates
+    fn lookup_extern(&self, module: &[String], name: &str) -> Option<Binding> {
+        let externs = self.collector.externs.get(&module[0])?;
+        externs.get(name).map(|c| Binding::Def(c.clone()))
+    }
+
+    fn lookup_in(&self, mut current: Binding, path: &[String]) -> Option<Binding> {
+        for seg in path {
+            current = match current {
+                Binding::Def(m) => self.scopes.get(&m)?.get(seg)?.clone(),
+                Binding::Extern(_) => Binding::Extern(path.last().unwrap().clone()),
+            };
+        }
+        Some(current)
+    }
+}
+
+/// Dependency graph over the canonical paths of the collected definitions. With
+/// `implementations`, traits depend on the types implementing them, which their trait objects
+/// may be.
+pub fn graph(collector: &Collector, implementations: bool) -> Graph {
+    let resolver = Resolver::new(collector);
+    let mut graph = Graph::new();
+    for (k, node) in &collector.items {
+        let mut edges = vec![];
+        for r in &node.refs {
+            let mut targets = vec![];
+            resolver.targets(&node.module, r, &mut vec![], &mut targets);
+            edges.extend(targets.into_iter().map(|(target, wrappers)| Edge {
+                target,
+                field: r.field.clone(),
+                variant: r.variant.clone(),
+                wrappers,
+                location: r.location.clone(),
+            }));
+        }
+        graph.insert(k.clone(), edges);
+    }
+    if implementations {
+        for i in &collector.impls {
+            let (ty, t) = match (&i.self_ty, &i.trait_) {
+                (Some(ty), Some(t)) => (ty, t),
+                _ => continue,
+            };
+            if let Some(edges) = graph.get_mut(&resolver.resolve(&i.module, t)) {
+                edges.push(Edge {
+                    target: resolver.resolve(&i.module, ty),
+                    field: None,
+                    variant: None,
+                    wrappers: vec![Wrapper::Implementation],
+                    location: i.location.clone(),
+                });
+            }
+        }
+    }
+    graph
+}
+
+pub fn last_segment(path: &str) -> &str {
+    path.rsplit("::").next().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rustc_session::parse::ParseSess;
+    use rustc_span::{edition::Edition, source_map::FilePathMapping};
+
+    use super::*;
+
+    /// Collects the crate whose root file contains `src`
+    fn collect(src: &str) -> Collector {
+        rustc_span::with_session_globals(Edition::Edition2018, || {
+            let sess = ParseSess::new(FilePathMapping::empty());
+            let name = PathBuf::from("lib.rs").into();
+            let krate = match rustc_parse::parse_crate_from_source_str(name, src.into(), &sess) {
+                Ok(krate) => krate,
+                Err(mut e) => {
+                    e.emit();
+                    panic!("invalid source");
+                }
+            };
+            let mut collector = Collector::default();
+            collector.collect_crate(vec!["crate".to_string()], &krate, &sess);
+            collector
+        })
+    }
+
+    /// Resolves `path` written in `module` of the crate whose root file contains `src`
+    fn resolve(src: &str, module: &str, path: &str) -> String {
+        let collector = collect(src);
+        let module: Vec<String> = module.split("::").map(String::from).collect();
+        let path: Vec<String> = path.split("::").map(String::from).collect();
+        Resolver::new(&collector).resolve(&module, &path)
+    }
+
+    /// Targets of the edges from `ty` in the graph of the crate whose root file contains `src`,
+    /// sorted
+    fn targets(src: &str, ty: &str) -> Vec<String> {
+        let graph = graph(&collect(src), false);
+        let mut targets: Vec<String> = graph[ty].iter().map(|e| e.target.clone()).collect();
+        targets.sort();
+        targets
+    }
+
+    #[test]
+    fn rename() {
+        let src = "mod a { pub struct X; } mod b { use crate::a::X as Y; }";
+        assert_eq!(resolve(src, "crate::b", "Y"), "crate::a::X");
+    }
+
+    #[test]
+    fn self_super_crate() {
+        let src = "mod a { pub struct X; pub mod b { pub struct Z; } }";
+        assert_eq!(resolve(src, "crate::a::b", "super::X"), "crate::a::X");
+        assert_eq!(resolve(src, "crate::a", "self::b::Z"), "crate::a::b::Z");
+        assert_eq!(resolve(src, "crate::a::b", "crate::a::X"), "crate::a::X");
+        assert_eq!(resolve(src, "crate::a::b", "super::super::a::b::Z"), "crate::a::b::Z");
+    }
+
+    #[test]
+    fn chained_globs() {
+        let src =
+            "mod a { pub struct X; } mod b { pub use super::a::*; } mod c { use crate::b::*; }";
+        assert_eq!(resolve(src, "crate::c", "X"), "crate::a::X");
+    }
+
+    #[test]
+    fn single_import_shadows_glob() {
+        let src = "mod x { pub struct E; } mod y { pub struct E; }
+                   mod m { use crate::x::*; use crate::y::E; }";
+        assert_eq!(resolve(src, "crate::m", "E"), "crate::y::E");
+    }
+
+    #[test]
+    fn conflicting_imports() {
+        let src = "mod x { pub struct E; } mod y { pub struct E; }
+                   mod m { use crate::x::E; use crate::y::E; }";
+        assert_eq!(resolve(src, "crate::m", "E"), "crate::x::E");
+    }
+
+    #[test]
+    fn imports_in_bodies() {
+        let src = "mod x { pub struct E; } mod y { pub struct E; }
+                   fn a() { use crate::x::E; } fn b() { use crate::y::E; }";
+        assert_eq!(resolve(src, "crate", "E"), "E");
+    }
+
+    #[test]
+    fn unresolved_paths() {
+        let src = "mod ui { pub struct Cell; pub struct Vec; pub struct T; } struct S;";
+        assert_eq!(resolve(src, "crate", "std::cell::Cell"), "Cell");
+        assert_eq!(resolve(src, "crate", "Vec"), "Vec");
+        assert_eq!(resolve(src, "crate", "T"), "T");
+        assert_eq!(resolve(src, "crate::ui", "Cell"), "crate::ui::Cell");
+    }
+
+    #[test]
+    fn projection_defined_in_impl() {
+        let src = "struct Cell<T>(T); trait Tr { type A; } struct Foo;
+                   impl Tr for Foo { type A = Cell<u8>; } struct S { x: <Foo as Tr>::A }";
+        assert_eq!(targets(src, "crate::S"), ["crate::Cell", "crate::Foo", "crate::Tr", "u8"]);
+    }
+
+    #[test]
+    fn projection_default_in_trait() {
+        let src = "struct Cell; trait Tr { type A = Cell; } struct Foo; impl Tr for Foo {}
+                   struct S { x: <Foo as Tr>::A }";
+        assert_eq!(targets(src, "crate::S"), ["crate::Cell", "crate::Foo", "crate::Tr"]);
+    }
+
+    #[test]
+    fn projection_cycle() {
+        let src = "trait Tr { type A; } struct Foo; impl Tr for Foo { type A = <Foo as Tr>::A; }
+                   struct S { x: <Foo as Tr>::A }";
+        // those of the inner projection, which is not expanded again
+        let expected = ["crate::Foo", "crate::Foo", "crate::Tr", "crate::Tr"];
+        assert_eq!(targets(src, "crate::S"), expected);
+    }
+
+    #[test]
+    fn projection_resolved_in_impl_module() {
+        let src = "trait Tr { type A; } struct Cell; struct S { x: <m::Foo as Tr>::A }
+                   mod m { pub struct Cell; pub struct Foo;
+                           impl crate::Tr for Foo { type A = Cell; } }";
+        assert_eq!(targets(src, "crate::S"), ["crate::Tr", "crate::m::Cell", "crate::m::Foo"]);
+    }
+
+    #[test]
+    fn shorthand_projections() {
+        let src = "struct Cell; trait Tr { type A; type B; } struct Foo;
+                   impl Tr for Foo { type A = Cell; type B = Box<Self::A>; }
+                   struct S { x: <Foo as Tr>::B } struct Item;
+                   struct T<I: Iterator> { x: I::Item, y: Vec<I> }";
+        assert_eq!(targets(src, "crate::S"), ["Box", "crate::Cell", "crate::Foo", "crate::Tr"]);
+        assert_eq!(targets(src, "crate::T"), ["I", "Vec"]);
+    }
+}